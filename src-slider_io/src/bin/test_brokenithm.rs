@@ -1,10 +1,15 @@
 extern crate slider_io;
 
-use std::io;
+use parking_lot::Mutex;
+use std::{io, sync::Arc};
 
 use slider_io::{
-  device::{brokenithm::BrokenithmJob, config::BrokenithmSpec},
-  shared::worker::AsyncHaltableWorker,
+  device::{
+    brokenithm::BrokenithmJob,
+    brokenithm_metrics::BrokenithmMetrics,
+    config::{BrokenithmConfig, BrokenithmSpec},
+  },
+  shared::{utils::ErrorLog, worker::AsyncHaltableWorker},
   state::SliderState,
 };
 
@@ -15,10 +20,23 @@ async fn main() {
     .init();
 
   let state = SliderState::new();
+  let brokenithm_config = Arc::new(Mutex::new(BrokenithmConfig::default()));
+  let brokenithm_metrics = Arc::new(BrokenithmMetrics::new());
 
   let _worker = AsyncHaltableWorker::new(
     "brokenithm",
-    BrokenithmJob::new(&state, &BrokenithmSpec::Nostalgia, &false, &1606),
+    BrokenithmJob::new(
+      &state,
+      &BrokenithmSpec::Nostalgia,
+      &false,
+      &5000,
+      &1606,
+      &vec!["0.0.0.0".parse().unwrap()],
+      &false,
+      &brokenithm_config,
+      &brokenithm_metrics,
+      &ErrorLog::new(),
+    ),
   );
   let mut input = String::new();
   io::stdin().read_line(&mut input).unwrap();
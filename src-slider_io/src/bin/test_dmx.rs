@@ -0,0 +1,82 @@
+extern crate slider_io;
+
+use std::{net::IpAddr, thread, time::Duration};
+
+use slider_io::{
+  lighting::dmx::DmxLightsJob,
+  output::dmx::DmxOutputJob,
+  shared::{
+    dmx::DmxProtocol,
+    utils::{ErrorLog, LoopTimer},
+    worker::AsyncWorker,
+  },
+  state::SliderState,
+};
+
+#[tokio::main]
+async fn main() {
+  env_logger::Builder::new()
+    .filter_level(log::LevelFilter::Debug)
+    .init();
+
+  let errors = ErrorLog::new();
+  let loopback: IpAddr = "127.0.0.1".parse().unwrap();
+
+  // Send the lights of one state over each protocol and receive them into
+  // another over loopback
+  let state = SliderState::new();
+  let mut received = vec![];
+  let mut workers = vec![];
+  for protocol in [DmxProtocol::Sacn, DmxProtocol::ArtNet] {
+    let received_state = SliderState::new();
+    workers.push(AsyncWorker::new(
+      "dmx-out",
+      DmxOutputJob::new(
+        &state,
+        &protocol,
+        &"127.0.0.1".to_string(),
+        &1,
+        &1,
+        &30,
+        &errors,
+      ),
+      LoopTimer::new(),
+    ));
+    workers.push(AsyncWorker::new(
+      "dmx-in",
+      DmxLightsJob::new(&received_state, &protocol, &1, &1, &vec![loopback], &errors),
+      LoopTimer::new(),
+    ));
+    received.push((protocol, received_state));
+  }
+
+  // Sweep a lit pixel across the slider and print where it arrives
+  let output_state = state.clone();
+  thread::spawn(move || {
+    for i in 0.. {
+      {
+        let mut lights_handle = output_state.lights.lock();
+        lights_handle.ground.fill(0);
+        lights_handle.ground[i % 31 * 3..i % 31 * 3 + 3].copy_from_slice(&[255, 128, 0]);
+        lights_handle.air_left.fill((i / 31 % 2) as u8 * 255);
+        lights_handle.dirty = true;
+      }
+      thread::sleep(Duration::from_millis(250));
+    }
+  });
+
+  loop {
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    for (protocol, received_state) in received.iter() {
+      let lights_handle = received_state.lights.lock();
+      let pixel = lights_handle
+        .ground
+        .chunks(3)
+        .position(|x| x.iter().any(|x| *x > 0));
+      println!(
+        "{:?} pixel {:?} air {:?}",
+        protocol, pixel, lights_handle.air_left
+      );
+    }
+  }
+}
@@ -0,0 +1,59 @@
+extern crate slider_io;
+
+use std::{net::UdpSocket, thread, time::Duration};
+
+use slider_io::{
+  output::{config::PollingRate, osc::OscJob},
+  shared::{
+    osc::OscMessage,
+    utils::{ErrorLog, LoopTimer},
+    worker::AsyncWorker,
+  },
+  state::SliderState,
+};
+
+#[tokio::main]
+async fn main() {
+  env_logger::Builder::new()
+    .filter_level(log::LevelFilter::Debug)
+    .init();
+
+  let socket = UdpSocket::bind("127.0.0.1:9000").unwrap();
+
+  let state = SliderState::new();
+  let _worker = AsyncWorker::new(
+    "osc",
+    OscJob::new(
+      &state,
+      &"127.0.0.1:9000".to_string(),
+      &PollingRate::Sixty,
+      &20,
+      &ErrorLog::new(),
+    ),
+    LoopTimer::new(),
+  );
+
+  // Sweep a finger across the slider and print what arrives
+  let input_state = state.clone();
+  thread::spawn(move || {
+    for i in 0.. {
+      {
+        let mut input_handle = input_state.input.lock();
+        input_handle.ground.fill(0);
+        input_handle.ground[i % 32] = 255;
+        input_handle.air[0] = (i / 32 % 2) as u8;
+      }
+      thread::sleep(Duration::from_millis(250));
+    }
+  });
+
+  let mut buf = [0; 2048];
+  loop {
+    let len = socket.recv(&mut buf).unwrap();
+    for message in OscMessage::parse_packet(&buf[..len]).unwrap_or_default() {
+      if message.addr.matches('/').count() > 2 {
+        println!("{} {:?}", message.addr, message.args);
+      }
+    }
+  }
+}
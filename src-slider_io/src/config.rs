@@ -3,15 +3,25 @@ use serde_json::Value;
 use std::fs;
 
 use crate::{
-  device::config::DeviceMode, lighting::config::LightsMode, output::config::OutputMode, system,
+  device::config::{BrokenithmConfig, DeviceMode},
+  lighting::config::{IdleFallback, LightsLayer, LightsMode},
+  output::config::OutputMode,
+  system,
 };
 
 #[derive(Debug, Clone)]
 pub struct Config {
   pub raw: String,
   pub device_mode: DeviceMode,
-  pub output_mode: OutputMode,
+  pub output_modes: Vec<OutputMode>,
   pub lights_mode: LightsMode,
+  pub lights_layers: Vec<LightsLayer>,
+  pub lights_idle: Option<IdleFallback>,
+  /// Brightness from 0 to 1 of the lights sent to the controller.
+  pub lights_brightness: f32,
+  /// Whether the slider lights are recorded to a file.
+  pub lights_record: bool,
+  pub brokenithm: BrokenithmConfig,
 }
 
 impl Config {
@@ -21,11 +31,30 @@ impl Config {
     Some(Config {
       raw: s.to_string(),
       device_mode: DeviceMode::from_serde_value(&v)?,
-      output_mode: OutputMode::from_serde_value(&v)?,
+      output_modes: OutputMode::list_from_serde_value(&v)?,
       lights_mode: LightsMode::from_serde_value(&v)?,
+      lights_layers: LightsLayer::list_from_serde_value(&v)?,
+      lights_idle: IdleFallback::from_serde_value(&v),
+      lights_brightness: (v["ledBrightness"]
+        .as_f64()
+        .unwrap_or(100.0)
+        .clamp(0.0, 100.0)
+        / 100.0) as f32,
+      lights_record: v["ledRecord"].as_bool().unwrap_or(false),
+      brokenithm: BrokenithmConfig::from_serde_value_or_default(&v["brokenithm"]),
     })
   }
 
+  /// Replaces the brokenithm section of the config, keeping the raw config in
+  /// sync so that it is saved.
+  pub fn set_brokenithm(&mut self, brokenithm: BrokenithmConfig) {
+    if let Ok(mut v) = serde_json::from_str::<Value>(self.raw.as_str()) {
+      v["brokenithm"] = brokenithm.to_serde_value();
+      self.raw = v.to_string();
+    }
+    self.brokenithm = brokenithm;
+  }
+
   fn default() -> Self {
     Self::from_str(
       r##"{
@@ -33,21 +62,78 @@ impl Config {
       "outputMode": "none",
       "ledMode": "none",
       "disableAirStrings": false,
+      "ledCorrection": {},
       "divaSerialPort": "COM1",
       "divaBrightness": 63,
       "brokenithmPort": 1606,
+      "brokenithmCustomLayout": "custom",
+      "brokenithmBindAddrs": "0.0.0.0",
+      "brokenithmTimeout": 5000,
       "keyboardSensitivity": 20,
       "keyboardDirectInput": false,
+      "keyboardDevice": 0,
+      "keyboardDeviceLayout": "default",
+      "gamepadDevice": "",
+      "gamepadDeviceLayout": "default",
+      "midiInPort": "",
+      "midiInLayout": "default",
       "outputPolling": "100",
       "outputWebsocketUrl": "localhost:3000",
+      "outputNetworkAddr": "192.168.1.2:1607",
+      "outputOscAddr": "127.0.0.1:9000",
+      "outputDmxAddr": "",
+      "outputDmxUniverse": 1,
+      "outputDmxStartChannel": 1,
+      "outputDmxRate": 30,
+      "outputOpenRgbAddr": "127.0.0.1:6742",
+      "outputOpenRgbDevice": "",
+      "outputOpenRgbZone": "",
+      "outputWledAddr": "",
+      "outputWledPixels": 60,
+      "outputWledAirPixels": 0,
+      "outputWledLayout": "stretch",
+      "outputWledRate": 30,
+      "mouseSpeed": 1000,
+      "mouseSmoothing": 50,
+      "extraOutputs": [],
+      "midiOutPort": "",
+      "midiOutChannel": 1,
+      "midiOutLayout": "default",
+      "networkPort": 1607,
+      "networkBindAddrs": "0.0.0.0",
       "ledFaster": false,
       "ledColorActive": "#ff00ff",
       "ledColorInactive": "#ffff00",
       "ledColorAirActive": "#0086ed",
       "ledColorAirInactive": "#000000",
       "ledSensitivity": 20,
+      "ledAfterglow": 0,
+      "ledRipple": 0,
+      "ledFadeCurve": "linear",
+      "ledEffect": "custom",
+      "ledScript": "custom",
       "ledWebsocketUrl": "localhost:3001",
-      "ledSerialPort": "COM5"
+      "ledUmgrWebsocketBindAddrs": "0.0.0.0",
+      "ledSerialPort": "COM5",
+      "ledLayers": [],
+      "ledDmxUniverse": 1,
+      "ledDmxStartChannel": 1,
+      "ledDmxBindAddrs": "0.0.0.0",
+      "ledIdleTimeout": 0,
+      "ledBrightness": 100,
+      "ledRecord": false,
+      "ledPlaybackFile": "",
+      "ledPlaybackLoop": true,
+      "ledPlaybackSpeed": 100,
+      "brokenithm": {
+        "invert": false,
+        "bgColor": "#000000",
+        "bgImage": false,
+        "keyColor": "#FF00FF",
+        "lkeyColor": "#00FFFF",
+        "ledOpacity": 1.0,
+        "keyHeight": 1.0
+      }
     }"##,
     )
     .unwrap()
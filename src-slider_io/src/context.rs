@@ -1,29 +1,106 @@
 use atomic_float::AtomicF64;
 use log::info;
+use parking_lot::Mutex;
 use std::sync::{atomic::Ordering, Arc};
 
 use crate::{
   config::Config,
-  device::{brokenithm::BrokenithmJob, config::DeviceMode, diva::DivaSliderJob, hid::HidJob},
-  lighting::{config::LightsMode, lighting::LightsJob, umgr_websocket::UmgrWebsocketJob},
-  output::{config::OutputMode, output::OutputJob},
+  device::{
+    brokenithm::BrokenithmJob,
+    brokenithm_metrics::{BrokenithmClientStatus, BrokenithmMetrics},
+    config::{BrokenithmConfig, DeviceMode},
+    diva::DivaSliderJob,
+    gamepad::GamepadJob,
+    hid::HidJob,
+    keyboard::KeyboardJob,
+    midi::MidiJob,
+    network::NetworkJob,
+  },
+  lighting::{
+    compositor::CompositorJob, config::LightsMode, dmx::DmxLightsJob, idle::IdleJob,
+    lighting::LightsJob, playback::PlaybackJob, record::RecordJob,
+    umgr_websocket::UmgrWebsocketJob,
+  },
+  output::{
+    config::OutputMode, dmx::DmxOutputJob, network::NetworkOutputJob, openrgb::OpenRgbJob,
+    osc::OscJob, output::OutputJob, wled::WledJob,
+  },
   shared::{
-    utils::LoopTimer,
+    utils::{ErrorLog, LoopTimer},
     worker::{AsyncHaltableWorker, AsyncWorker, ThreadWorker},
   },
   state::SliderState,
 };
 
+/// Starts the worker for a lighting mode painting into `state`.
+fn spawn_lights(
+  state: &SliderState,
+  mode: &LightsMode,
+  errors: &ErrorLog,
+  timers: &mut Vec<(&'static str, Arc<AtomicF64>)>,
+  lights_workers: &mut Vec<AsyncWorker>,
+  lights_haltable_workers: &mut Vec<AsyncHaltableWorker>,
+) {
+  match mode {
+    LightsMode::None => {}
+    LightsMode::UmgrWebsocket { faster, port, bind } => {
+      lights_haltable_workers.push(AsyncHaltableWorker::new(
+        "lights",
+        UmgrWebsocketJob::new(state, faster, port, bind, errors),
+      ));
+    }
+    LightsMode::Dmx {
+      protocol,
+      universe,
+      start_channel,
+      bind,
+    } => {
+      let timer = LoopTimer::new();
+      timers.push(("l", timer.fork()));
+      lights_workers.push(AsyncWorker::new(
+        "lights",
+        DmxLightsJob::new(state, protocol, universe, start_channel, bind, errors),
+        timer,
+      ));
+    }
+    LightsMode::Playback {
+      file,
+      looped,
+      speed,
+    } => {
+      let timer = LoopTimer::new();
+      timers.push(("l", timer.fork()));
+      lights_workers.push(AsyncWorker::new(
+        "lights",
+        PlaybackJob::new(state, file, looped, speed, errors),
+        timer,
+      ));
+    }
+    _ => {
+      let timer = LoopTimer::new();
+      timers.push(("l", timer.fork()));
+      lights_workers.push(AsyncWorker::new(
+        "lights",
+        LightsJob::new(state, mode, errors),
+        timer,
+      ));
+    }
+  }
+}
+
 #[allow(dead_code)]
 pub struct Context {
   state: SliderState,
   config: Config,
+  brokenithm_config: Arc<Mutex<BrokenithmConfig>>,
+  brokenithm_metrics: Arc<BrokenithmMetrics>,
+  errors: ErrorLog,
   device_thread_worker: Option<ThreadWorker>,
   device_async_worker: Option<AsyncWorker>,
   device_async_haltable_worker: Option<AsyncHaltableWorker>,
-  output_worker: Option<AsyncWorker>,
-  lights_worker: Option<AsyncWorker>,
-  lights_haltable_worker: Option<AsyncHaltableWorker>,
+  output_workers: Vec<AsyncWorker>,
+  lights_workers: Vec<AsyncWorker>,
+  lights_haltable_workers: Vec<AsyncHaltableWorker>,
   timers: Vec<(&'static str, Arc<AtomicF64>)>,
 }
 
@@ -31,10 +108,16 @@ impl Context {
   pub fn new(config: Config) -> Self {
     info!("Context creating");
     info!("Device config {:?}", config.device_mode);
-    info!("Output config {:?}", config.output_mode);
+    info!("Output config {:?}", config.output_modes);
     info!("Lights config {:?}", config.lights_mode);
+    info!("Lights layers {:?}", config.lights_layers);
+    info!("Lights idle {:?}", config.lights_idle);
 
     let state = SliderState::new();
+    state.lights.lock().brightness = config.lights_brightness;
+    let brokenithm_config = Arc::new(Mutex::new(config.brokenithm.clone()));
+    let brokenithm_metrics = Arc::new(BrokenithmMetrics::new());
+    let errors = ErrorLog::new();
     let mut timers = vec![];
 
     let (device_thread_worker, device_async_worker, device_async_haltable_worker) =
@@ -43,22 +126,40 @@ impl Context {
         DeviceMode::Brokenithm {
           spec,
           lights_enabled,
+          timeout_ms,
           port,
+          qr_direct,
+          bind,
         } => (
           None,
           None,
           Some(AsyncHaltableWorker::new(
             "brokenithm",
-            BrokenithmJob::new(&state, spec, lights_enabled, port),
+            BrokenithmJob::new(
+              &state,
+              spec,
+              lights_enabled,
+              timeout_ms,
+              port,
+              bind,
+              qr_direct,
+              &brokenithm_config,
+              &brokenithm_metrics,
+              &errors,
+            ),
           )),
         ),
-        DeviceMode::Hardware { spec, disable_air } => (
+        DeviceMode::Hardware {
+          spec,
+          disable_air,
+          correction,
+        } => (
           {
             let timer = LoopTimer::new();
             timers.push(("d", timer.fork()));
             Some(ThreadWorker::new(
               "device",
-              HidJob::from_config(&state, spec, disable_air),
+              HidJob::from_config(&state, spec, disable_air, correction),
               timer,
             ))
           },
@@ -78,51 +179,227 @@ impl Context {
           None,
           None,
         ),
+        DeviceMode::Keyboard { device, layout } => (
+          {
+            let timer = LoopTimer::new();
+            timers.push(("d", timer.fork()));
+            Some(ThreadWorker::new(
+              "keyboard",
+              KeyboardJob::new(&state, device, layout, &errors),
+              timer,
+            ))
+          },
+          None,
+          None,
+        ),
+        DeviceMode::Gamepad { device, layout } => (
+          {
+            let timer = LoopTimer::new();
+            timers.push(("d", timer.fork()));
+            Some(ThreadWorker::new(
+              "gamepad",
+              GamepadJob::new(&state, device, layout, &errors),
+              timer,
+            ))
+          },
+          None,
+          None,
+        ),
+        DeviceMode::Network {
+          transport,
+          port,
+          bind,
+        } => (
+          None,
+          None,
+          Some(AsyncHaltableWorker::new(
+            "network",
+            NetworkJob::new(&state, transport, port, bind, &errors),
+          )),
+        ),
+        DeviceMode::Midi { port, layout } => (
+          None,
+          None,
+          Some(AsyncHaltableWorker::new(
+            "midi",
+            MidiJob::new(&state, port, layout, &errors),
+          )),
+        ),
       };
-    let output_worker = match &config.output_mode {
-      OutputMode::None => None,
-      _ => {
-        let timer = LoopTimer::new();
-        timers.push(("o", timer.fork()));
-        Some(AsyncWorker::new(
+    let mut output_workers = vec![];
+    for output_mode in config.output_modes.iter() {
+      if let OutputMode::None = output_mode {
+        continue;
+      }
+
+      let timer = LoopTimer::new();
+      timers.push(("o", timer.fork()));
+      output_workers.push(match output_mode {
+        OutputMode::Network {
+          transport,
+          addr,
+          polling,
+        } => AsyncWorker::new(
           "output",
-          OutputJob::new(&state, &config.output_mode),
+          NetworkOutputJob::new(&state, transport, addr, polling),
           timer,
-        ))
-      }
+        ),
+        OutputMode::Osc {
+          addr,
+          polling,
+          sensitivity,
+        } => AsyncWorker::new(
+          "output",
+          OscJob::new(&state, addr, polling, sensitivity, &errors),
+          timer,
+        ),
+        OutputMode::Dmx {
+          protocol,
+          addr,
+          universe,
+          start_channel,
+          rate,
+        } => AsyncWorker::new(
+          "output",
+          DmxOutputJob::new(
+            &state,
+            protocol,
+            addr,
+            universe,
+            start_channel,
+            rate,
+            &errors,
+          ),
+          timer,
+        ),
+        OutputMode::OpenRgb { addr, device, zone } => AsyncWorker::new(
+          "output",
+          OpenRgbJob::new(&state, addr, device, zone, &errors),
+          timer,
+        ),
+        OutputMode::Wled {
+          protocol,
+          addr,
+          pixels,
+          air_pixels,
+          layout,
+          rate,
+        } => AsyncWorker::new(
+          "output",
+          WledJob::new(
+            &state, protocol, addr, pixels, air_pixels, layout, rate, &errors,
+          ),
+          timer,
+        ),
+        _ => AsyncWorker::new(
+          "output",
+          OutputJob::new(&state, output_mode, &errors),
+          timer,
+        ),
+      });
+    }
+    let mut lights_workers = vec![];
+    let mut lights_haltable_workers = vec![];
+
+    // With the idle fallback, lighting renders into its own lights and the
+    // idle job picks between them and the attract lights
+    let idle = match (&config.lights_mode, config.lights_layers.len()) {
+      (LightsMode::None, 0) => None,
+      _ => config.lights_idle.as_ref(),
+    };
+    let lit = match idle {
+      Some(_) => state.fork_lights(),
+      None => state.clone(),
     };
-    let (lights_worker, lights_haltable_worker) = match &config.lights_mode {
-      LightsMode::None => (None, None),
-      LightsMode::UmgrWebsocket { faster, port } => (
-        None,
-        Some(AsyncHaltableWorker::new(
-          "lights",
-          UmgrWebsocketJob::new(&state, faster, port),
-        )),
+
+    match config.lights_layers.len() {
+      0 => spawn_lights(
+        &lit,
+        &config.lights_mode,
+        &errors,
+        &mut timers,
+        &mut lights_workers,
+        &mut lights_haltable_workers,
       ),
       _ => {
+        // Every layer renders into its own lights and the compositor blends
+        // them into the slider lights
+        let base = lit.fork_lights();
+        spawn_lights(
+          &base,
+          &config.lights_mode,
+          &errors,
+          &mut timers,
+          &mut lights_workers,
+          &mut lights_haltable_workers,
+        );
+
+        let mut layers = vec![];
+        for layer in config.lights_layers.iter() {
+          let layer_state = lit.fork_lights();
+          spawn_lights(
+            &layer_state,
+            &layer.mode,
+            &errors,
+            &mut timers,
+            &mut lights_workers,
+            &mut lights_haltable_workers,
+          );
+          layers.push((layer.clone(), layer_state));
+        }
+
         let timer = LoopTimer::new();
-        timers.push(("l", timer.fork()));
-        (
-          Some(AsyncWorker::new(
-            "lights",
-            LightsJob::new(&state, &config.lights_mode),
-            timer,
-          )),
-          None,
-        )
+        timers.push(("c", timer.fork()));
+        lights_workers.push(AsyncWorker::new(
+          "compositor",
+          CompositorJob::new(&lit, &base, &layers),
+          timer,
+        ));
       }
-    };
+    }
+
+    if let Some(idle) = idle {
+      let attract = state.fork_lights();
+      spawn_lights(
+        &attract,
+        &idle.attract,
+        &errors,
+        &mut timers,
+        &mut lights_workers,
+        &mut lights_haltable_workers,
+      );
+
+      let timer = LoopTimer::new();
+      timers.push(("i", timer.fork()));
+      lights_workers.push(AsyncWorker::new(
+        "idle",
+        IdleJob::new(&state, &lit, &attract, idle),
+        timer,
+      ));
+    }
+
+    if config.lights_record {
+      let timer = LoopTimer::new();
+      timers.push(("r", timer.fork()));
+      lights_workers.push(AsyncWorker::new(
+        "record",
+        RecordJob::new(&state, &errors),
+        timer,
+      ));
+    }
 
     Self {
       state,
       config,
+      brokenithm_config,
+      brokenithm_metrics,
+      errors,
       device_thread_worker,
       device_async_worker,
       device_async_haltable_worker,
-      output_worker,
-      lights_worker,
-      lights_haltable_worker,
+      output_workers,
+      lights_workers,
+      lights_haltable_workers,
       timers,
     }
   }
@@ -131,6 +408,22 @@ impl Context {
     self.state.clone()
   }
 
+  /// Updates brokenithm web controller options without restarting the server.
+  /// Clients pick up the new options on their next page load.
+  pub fn update_brokenithm_config(&self, brokenithm_config: BrokenithmConfig) {
+    let mut config_handle = self.brokenithm_config.lock();
+    *config_handle = brokenithm_config;
+  }
+
+  pub fn brokenithm_status(&self) -> Vec<BrokenithmClientStatus> {
+    self.brokenithm_metrics.status()
+  }
+
+  /// Errors raised by workers since the context was created, one per line.
+  pub fn error_state(&self) -> String {
+    self.errors.snapshot().join("\n")
+  }
+
   pub fn timer_state(&self) -> String {
     self
       .timers
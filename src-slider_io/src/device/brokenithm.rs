@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use futures::{SinkExt, StreamExt};
+use futures::{future, FutureExt, SinkExt, StreamExt};
 use hyper::{
   header,
   server::conn::AddrStream,
@@ -8,17 +8,32 @@ use hyper::{
   Body, Method, Request, Response, Server, StatusCode,
 };
 use log::{error, info};
+use parking_lot::Mutex;
 use phf::phf_map;
-use std::{convert::Infallible, future::Future, net::SocketAddr};
+use std::{
+  convert::Infallible,
+  future::Future,
+  net::{IpAddr, SocketAddr},
+  path::{Path, PathBuf},
+  sync::Arc,
+};
 use tokio::{
-  select,
+  fs, select,
   sync::mpsc,
-  time::{sleep, Duration},
+  time::{sleep, Duration, Instant},
 };
 use tokio_tungstenite::WebSocketStream;
 use tungstenite::{handshake, Message};
 
-use crate::{device::config::BrokenithmSpec, shared::worker::AsyncHaltableJob, state::SliderState};
+use crate::{
+  device::{
+    brokenithm_metrics::BrokenithmMetrics,
+    config::{BrokenithmConfig, BrokenithmSpec},
+  },
+  shared::{net, utils::ErrorLog, worker::AsyncHaltableJob},
+  state::SliderState,
+  system::{self, QrFormat},
+};
 
 // https://levelup.gitconnected.com/handling-websocket-and-http-on-the-same-port-with-rust-f65b770722c9
 
@@ -35,7 +50,6 @@ async fn error_response() -> Result<Response<Body>, Infallible> {
 
 static BROKENITHM_STR_FILES: phf::Map<&'static str, (&'static str, &'static str)> = phf_map! {
   "app.js" => (include_str!("./brokenithm-www/app.js"), "text/javascript"),
-  "config.js" => (include_str!("./brokenithm-www/config.js"), "text/javascript"),
   "index-ns.html" => (include_str!("./brokenithm-www/index-ns.html"), "text/html"),
   "index-go.html" => (include_str!("./brokenithm-www/index-go.html"), "text/html"),
   "index.html" => (include_str!("./brokenithm-www/index.html"), "text/html"),
@@ -46,7 +60,53 @@ static BROKENITHM_BIN_FILES: phf::Map<&'static str, (&'static [u8], &'static str
   "icon.png" => (include_bytes!("./brokenithm-www/icon.png"), "image/png"),
 };
 
-async fn serve_file(path: &str) -> Result<Response<Body>, Infallible> {
+fn guess_mime(path: &str) -> &'static str {
+  match Path::new(path).extension().and_then(|x| x.to_str()) {
+    Some("html") | Some("htm") => "text/html",
+    Some("js") => "text/javascript",
+    Some("css") => "text/css",
+    Some("json") => "application/json",
+    Some("png") => "image/png",
+    Some("jpg") | Some("jpeg") => "image/jpeg",
+    Some("gif") => "image/gif",
+    Some("svg") => "image/svg+xml",
+    Some("webp") => "image/webp",
+    Some("ico") => "image/x-icon",
+    Some("woff") => "font/woff",
+    Some("woff2") => "font/woff2",
+    _ => "application/octet-stream",
+  }
+}
+
+/// Looks for a user supplied file in the brokenithm override directory.
+async fn get_override_path(www_dir: &Option<PathBuf>, path: &str) -> Option<PathBuf> {
+  let file_path = system::join_config_path(www_dir.as_ref()?, path)?;
+  match fs::metadata(file_path.as_path()).await {
+    Ok(metadata) if metadata.is_file() => Some(file_path),
+    _ => None,
+  }
+}
+
+async fn serve_file(www_dir: &Option<PathBuf>, path: &str) -> Result<Response<Body>, Infallible> {
+  if let Some(file_path) = get_override_path(www_dir, path).await {
+    match fs::read(file_path.as_path()).await {
+      Ok(data) => {
+        return Ok(
+          Response::builder()
+            .header(header::CONTENT_TYPE, guess_mime(path))
+            .body(Body::from(data))
+            .unwrap(),
+        );
+      }
+      Err(e) => {
+        error!(
+          "Brokenithm override {:?} could not be read: {}",
+          file_path, e
+        );
+      }
+    }
+  }
+
   match (
     BROKENITHM_STR_FILES.get(path),
     BROKENITHM_BIN_FILES.get(path),
@@ -67,12 +127,102 @@ async fn serve_file(path: &str) -> Result<Response<Body>, Infallible> {
   }
 }
 
+/// Serves the web controller config generated from the current brokenithm
+/// options, unless the user has supplied their own file.
+async fn serve_config(
+  www_dir: &Option<PathBuf>,
+  path: &str,
+  brokenithm_config: Arc<Mutex<BrokenithmConfig>>,
+) -> Result<Response<Body>, Infallible> {
+  if get_override_path(www_dir, path).await.is_some() {
+    return serve_file(www_dir, path).await;
+  }
+
+  let (data, mime) = {
+    let config_handle = brokenithm_config.lock();
+    match path {
+      "config.json" => (
+        config_handle.to_serde_value().to_string(),
+        "application/json",
+      ),
+      _ => (config_handle.to_js(), "text/javascript"),
+    }
+  };
+
+  Ok(
+    Response::builder()
+      .header(header::CONTENT_TYPE, mime)
+      .header(header::CACHE_CONTROL, "no-store")
+      .body(Body::from(data))
+      .unwrap(),
+  )
+}
+
+/// Serves a QR code linking to this server. Pass `?direct=1` or `?direct=0` to
+/// override whether the link points straight at the LAN address.
+async fn serve_qr(
+  request: &Request<Body>,
+  port: u16,
+  bind: &[IpAddr],
+  qr_direct: bool,
+  format: QrFormat,
+) -> Result<Response<Body>, Infallible> {
+  let direct = request
+    .uri()
+    .query()
+    .unwrap_or("")
+    .split('&')
+    .find_map(|x| match x {
+      "direct=1" | "direct=true" => Some(true),
+      "direct=0" | "direct=false" => Some(false),
+      _ => None,
+    })
+    .unwrap_or(qr_direct);
+
+  match system::get_brokenithm_qr(Some(port), bind, direct, format) {
+    Some(data) => Ok(
+      Response::builder()
+        .header(
+          header::CONTENT_TYPE,
+          match format {
+            QrFormat::Png => "image/png",
+            QrFormat::Svg => "image/svg+xml",
+          },
+        )
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(data))
+        .unwrap(),
+    ),
+    None => {
+      error!("Brokenithm QR could not be generated");
+      error_response().await
+    }
+  }
+}
+
+/// Releases the input of a client that went away. All clients share one
+/// input, so it is left alone while another client is connected and may be
+/// holding keys.
+fn release_input(state: &SliderState, metrics: &BrokenithmMetrics, addr: &SocketAddr) {
+  if metrics.has_other_clients(addr) {
+    return;
+  }
+
+  let mut input_handle = state.input.lock();
+  input_handle.ground.fill(0);
+  input_handle.air.fill(0);
+}
+
 async fn handle_brokenithm(
   ws_stream: WebSocketStream<Upgraded>,
+  remote_addr: SocketAddr,
   state: SliderState,
   lights_enabled: bool,
+  timeout: Duration,
+  metrics: Arc<BrokenithmMetrics>,
 ) {
   let (mut ws_write, mut ws_read) = ws_stream.split();
+  metrics.connect(&remote_addr);
 
   let (msg_write, mut msg_read) = mpsc::unbounded_channel::<Message>();
 
@@ -94,8 +244,12 @@ async fn handle_brokenithm(
     // info!("Websocket write task done");
   };
 
+  let last_active = Arc::new(Mutex::new(Instant::now()));
+
   let msg_write_handle = msg_write.clone();
   let state_handle = state.clone();
+  let metrics_handle = Arc::clone(&metrics);
+  let last_active_handle = Arc::clone(&last_active);
   let read_task = async move {
     // info!("Websocket read task open");
     loop {
@@ -103,52 +257,49 @@ async fn handle_brokenithm(
         Some(msg) => match msg {
           Ok(msg) => match msg {
             Message::Text(msg) => {
+              *last_active_handle.lock() = Instant::now();
               let chars = msg.chars().collect::<Vec<char>>();
 
               match chars.len() {
-                6 => {
-                  if chars[0] == 'a' {
-                    msg_write_handle
-                      .send(Message::Text("alive".to_string()))
-                      .ok();
-                  }
+                6 if chars[0] == 'a' => {
+                  msg_write_handle
+                    .send(Message::Text("alive".to_string()))
+                    .ok();
                 }
-                39 => {
-                  if chars[0] == 'b' {
-                    let mut input_handle = state_handle.input.lock();
-                    for (idx, c) in chars[1..33].iter().enumerate() {
-                      input_handle.ground[idx] = match *c == '1' {
-                        false => 0,
-                        true => 255,
-                      }
+                39 if chars[0] == 'b' => {
+                  metrics_handle.record_frame(&remote_addr);
+                  let mut input_handle = state_handle.input.lock();
+                  for (idx, c) in chars[1..33].iter().enumerate() {
+                    input_handle.ground[idx] = match *c == '1' {
+                      false => 0,
+                      true => 255,
                     }
-                    for (idx, c) in chars[33..39].iter().enumerate() {
-                      input_handle.air[idx] = match *c == '1' {
-                        false => 0,
-                        true => 1,
-                      }
+                  }
+                  for (idx, c) in chars[33..39].iter().enumerate() {
+                    input_handle.air[idx] = match *c == '1' {
+                      false => 0,
+                      true => 1,
                     }
                   }
                 }
                 _ => {
-                  break;
+                  metrics_handle.record_malformed(&remote_addr);
                 }
               }
             }
+            Message::Pong(payload) => {
+              metrics_handle.record_pong(&remote_addr, &payload);
+            }
             Message::Close(_) => {
               info!("Websocket connection closed");
-              let mut input_handle = state_handle.input.lock();
-              input_handle.ground.fill(0);
-              input_handle.air.fill(0);
+              release_input(&state_handle, &metrics_handle, &remote_addr);
               break;
             }
             _ => {}
           },
           Err(e) => {
             error!("Websocket connection error: {}", e);
-            let mut input_handle = state_handle.input.lock();
-            input_handle.ground.fill(0);
-            input_handle.air.fill(0);
+            release_input(&state_handle, &metrics_handle, &remote_addr);
             break;
           }
         },
@@ -160,11 +311,41 @@ async fn handle_brokenithm(
     // info!("Websocket read task done");
   };
 
+  let msg_write_handle = msg_write.clone();
+  let metrics_handle = Arc::clone(&metrics);
+  let ping_task = async move {
+    loop {
+      sleep(Duration::from_millis(1000)).await;
+      let payload = metrics_handle.record_ping(&remote_addr);
+      msg_write_handle.send(Message::Ping(payload)).ok();
+    }
+  };
+
+  // Pongs are answered by the browser even when the page is frozen, so only
+  // messages sent by the controller script count as activity
+  let state_handle = state.clone();
+  let metrics_handle = Arc::clone(&metrics);
+  let timeout_task = async move {
+    if timeout.is_zero() {
+      future::pending::<()>().await;
+    }
+    loop {
+      sleep(Duration::from_millis(100)).await;
+      if last_active.lock().elapsed() > timeout {
+        info!("Brokenithm client {} timed out", remote_addr);
+        release_input(&state_handle, &metrics_handle, &remote_addr);
+        break;
+      }
+    }
+  };
+
   match lights_enabled {
     false => {
       select! {
         _ = read_task => {}
         _ = write_task => {}
+        _ = ping_task => {}
+        _ = timeout_task => {}
       };
     }
     true => {
@@ -175,7 +356,7 @@ async fn handle_brokenithm(
           let mut lights_data = vec![0; 93];
           {
             let lights_handle = state_handle.lights.lock();
-            (&mut lights_data).copy_from_slice(&lights_handle.ground);
+            (&mut lights_data).copy_from_slice(&lights_handle.output().ground);
           }
           msg_write_handle.send(Message::Binary(lights_data)).ok();
 
@@ -186,16 +367,23 @@ async fn handle_brokenithm(
       select! {
         _ = read_task => {}
         _ = write_task => {}
+        _ = ping_task => {}
+        _ = timeout_task => {}
         _ = lights_task => {}
       };
     }
   }
+
+  metrics.disconnect(&remote_addr);
 }
 
 async fn handle_websocket(
   mut request: Request<Body>,
+  remote_addr: SocketAddr,
   state: SliderState,
   lights_enabled: bool,
+  timeout: Duration,
+  metrics: Arc<BrokenithmMetrics>,
 ) -> Result<Response<Body>, Infallible> {
   let res = match handshake::server::create_response_with_body(&request, || Body::empty()) {
     Ok(res) => {
@@ -209,7 +397,15 @@ async fn handle_websocket(
             )
             .await;
 
-            handle_brokenithm(ws_stream, state, lights_enabled).await;
+            handle_brokenithm(
+              ws_stream,
+              remote_addr,
+              state,
+              lights_enabled,
+              timeout,
+              metrics,
+            )
+            .await;
           }
 
           Err(e) => {
@@ -234,9 +430,7 @@ async fn handle_websocket(
 async fn handle_request(
   request: Request<Body>,
   remote_addr: SocketAddr,
-  state: SliderState,
-  spec: BrokenithmSpec,
-  lights_enabled: bool,
+  job: BrokenithmJob,
 ) -> Result<Response<Body>, Infallible> {
   let method = request.method();
   let path = request.uri().path();
@@ -253,22 +447,56 @@ async fn handle_request(
     request.uri().path(),
     request.headers().contains_key(header::UPGRADE),
   ) {
-    ("/", false) | ("/index.html", false) => match spec {
-      BrokenithmSpec::Basic => serve_file("index.html").await,
-      BrokenithmSpec::GroundOnly => serve_file("index-go.html").await,
-      BrokenithmSpec::Nostalgia => serve_file("index-ns.html").await,
+    ("/", false) | ("/index.html", false) => match job.spec {
+      BrokenithmSpec::Basic => serve_file(&job.www_dir, "index.html").await,
+      BrokenithmSpec::GroundOnly => serve_file(&job.www_dir, "index-go.html").await,
+      BrokenithmSpec::Nostalgia => serve_file(&job.www_dir, "index-ns.html").await,
+      BrokenithmSpec::Custom(name) => serve_file(&job.www_dir, &format!("{}.html", name)).await,
     },
-    (filename, false) => serve_file(&filename[1..]).await,
-    ("/ws", true) => handle_websocket(request, state, lights_enabled).await,
+    ("/qr", false) | ("/qr.png", false) => {
+      serve_qr(&request, job.port, &job.bind, job.qr_direct, QrFormat::Png).await
+    }
+    ("/qr.svg", false) => {
+      serve_qr(&request, job.port, &job.bind, job.qr_direct, QrFormat::Svg).await
+    }
+    ("/config.js", false) | ("/config.json", false) => {
+      serve_config(
+        &job.www_dir,
+        &request.uri().path()[1..],
+        job.brokenithm_config,
+      )
+      .await
+    }
+    (filename, false) => serve_file(&job.www_dir, &filename[1..]).await,
+    ("/ws", true) => {
+      handle_websocket(
+        request,
+        remote_addr,
+        job.state,
+        job.lights_enabled,
+        job.timeout,
+        job.metrics,
+      )
+      .await
+    }
     _ => error_response().await,
   }
 }
 
+#[derive(Clone)]
 pub struct BrokenithmJob {
   state: SliderState,
   spec: BrokenithmSpec,
   lights_enabled: bool,
+  timeout: Duration,
   port: u16,
+  bind: Vec<IpAddr>,
+  qr_direct: bool,
+  brokenithm_config: Arc<Mutex<BrokenithmConfig>>,
+  metrics: Arc<BrokenithmMetrics>,
+  /// Directory of user supplied web files, found when the server starts.
+  www_dir: Option<PathBuf>,
+  errors: ErrorLog,
 }
 
 impl BrokenithmJob {
@@ -276,13 +504,26 @@ impl BrokenithmJob {
     state: &SliderState,
     spec: &BrokenithmSpec,
     lights_enabled: &bool,
+    timeout_ms: &u64,
     port: &u16,
+    bind: &Vec<IpAddr>,
+    qr_direct: &bool,
+    brokenithm_config: &Arc<Mutex<BrokenithmConfig>>,
+    metrics: &Arc<BrokenithmMetrics>,
+    errors: &ErrorLog,
   ) -> Self {
     Self {
       state: state.clone(),
       spec: spec.clone(),
       lights_enabled: *lights_enabled,
+      timeout: Duration::from_millis(*timeout_ms),
       port: *port,
+      bind: bind.clone(),
+      qr_direct: *qr_direct,
+      brokenithm_config: Arc::clone(brokenithm_config),
+      metrics: Arc::clone(metrics),
+      www_dir: None,
+      errors: errors.clone(),
     }
   }
 }
@@ -290,34 +531,55 @@ impl BrokenithmJob {
 #[async_trait]
 impl AsyncHaltableJob for BrokenithmJob {
   async fn run<F: Future<Output = ()> + Send>(self, stop_signal: F) {
-    let state = self.state.clone();
-    let spec = self.spec.clone();
-    let lights_enabled = self.lights_enabled;
+    let mut job = self.clone();
+    job.www_dir = system::get_brokenithm_www_dir().map(|x| *x);
     let make_svc = make_service_fn(|conn: &AddrStream| {
       let remote_addr = conn.remote_addr();
-      let make_svc_state = state.clone();
-      let make_spec = spec.clone();
+      let make_job = job.clone();
       async move {
         Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
-          let svc_state = make_svc_state.clone();
-          let spec = make_spec.clone();
-          handle_request(request, remote_addr, svc_state, spec, lights_enabled)
+          handle_request(request, remote_addr, make_job.clone())
         }))
       }
     });
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], self.port));
-    info!("Brokenithm server listening on {}", addr);
+    let stop_signal = stop_signal.shared();
+    let mut servers = vec![];
+    for addr in self.bind.iter().map(|ip| SocketAddr::new(*ip, self.port)) {
+      let builder = match net::bind_tcp(&addr) {
+        Ok(listener) => match Server::from_tcp(listener) {
+          Ok(builder) => builder,
+          Err(e) => {
+            self.errors.push(format!(
+              "Brokenithm server cannot listen on {}: {}",
+              addr, e
+            ));
+            continue;
+          }
+        },
+        Err(e) => {
+          self
+            .errors
+            .push(net::describe_bind_error("Brokenithm server", &addr, &e));
+          continue;
+        }
+      };
+      info!("Brokenithm server listening on {}", addr);
 
-    let server = Server::bind(&addr)
-      // .http1_keepalive(false)
-      // .http2_keep_alive_interval(None)
-      // .tcp_keepalive(None)
-      .serve(make_svc)
-      .with_graceful_shutdown(stop_signal);
+      servers.push(
+        builder
+          // .http1_keepalive(false)
+          // .http2_keep_alive_interval(None)
+          // .tcp_keepalive(None)
+          .serve(make_svc.clone())
+          .with_graceful_shutdown(stop_signal.clone()),
+      );
+    }
 
-    if let Err(e) = server.await {
-      info!("Brokenithm server stopped: {}", e);
+    for res in future::join_all(servers).await {
+      if let Err(e) = res {
+        info!("Brokenithm server stopped: {}", e);
+      }
     }
   }
 }
@@ -0,0 +1,207 @@
+use log::info;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+  collections::HashMap,
+  fmt,
+  net::SocketAddr,
+  time::{Duration, Instant},
+};
+
+/// Connection quality counters for a single brokenithm client, collected on
+/// the server side.
+struct ClientMetrics {
+  connected: Instant,
+  last_frame: Option<Instant>,
+  frames: u64,
+  malformed: u64,
+
+  fps: f64,
+  fps_frames: u64,
+  fps_started: Instant,
+
+  rtt: Option<Duration>,
+  pending_ping: Option<(u64, Instant)>,
+  pings: u64,
+  lost_pings: u64,
+}
+
+impl ClientMetrics {
+  fn new() -> Self {
+    let now = Instant::now();
+    Self {
+      connected: now,
+      last_frame: None,
+      frames: 0,
+      malformed: 0,
+      fps: 0.0,
+      fps_frames: 0,
+      fps_started: now,
+      rtt: None,
+      pending_ping: None,
+      pings: 0,
+      lost_pings: 0,
+    }
+  }
+
+  fn update_fps(&mut self, now: Instant) {
+    let elapsed = now - self.fps_started;
+    if elapsed >= Duration::from_secs(1) {
+      self.fps = self.fps_frames as f64 / elapsed.as_secs_f64();
+      self.fps_frames = 0;
+      self.fps_started = now;
+    }
+  }
+
+  fn status(&self, addr: &SocketAddr) -> BrokenithmClientStatus {
+    let now = Instant::now();
+    // Report 0 fps if frames stopped arriving since the last full window
+    let fps = match now - self.fps_started > Duration::from_secs(2) {
+      true => 0.0,
+      false => self.fps,
+    };
+
+    BrokenithmClientStatus {
+      addr: addr.to_string(),
+      rtt_ms: self.rtt.map(|x| x.as_secs_f64() * 1000.0),
+      fps,
+      frames: self.frames,
+      malformed: self.malformed,
+      pings: self.pings,
+      lost_pings: self.lost_pings,
+      last_frame_ms: self.last_frame.map(|x| (now - x).as_secs_f64() * 1000.0),
+      connected_s: (now - self.connected).as_secs_f64(),
+    }
+  }
+}
+
+/// Snapshot of the connection quality of a brokenithm client.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokenithmClientStatus {
+  pub addr: String,
+  /// Round trip time of the latest answered websocket ping.
+  pub rtt_ms: Option<f64>,
+  /// Input frames received per second.
+  pub fps: f64,
+  pub frames: u64,
+  /// Frames that could not be understood and were ignored.
+  pub malformed: u64,
+  pub pings: u64,
+  /// Pings that were not answered before the next ping was sent.
+  pub lost_pings: u64,
+  /// Time since the last input frame was received.
+  pub last_frame_ms: Option<f64>,
+  pub connected_s: f64,
+}
+
+impl fmt::Display for BrokenithmClientStatus {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{} rtt:{} fps:{:.1} frames:{} malformed:{} lost:{}/{} up:{:.0}s",
+      self.addr,
+      self
+        .rtt_ms
+        .map(|x| format!("{:.1}ms", x))
+        .unwrap_or("-".to_string()),
+      self.fps,
+      self.frames,
+      self.malformed,
+      self.lost_pings,
+      self.pings,
+      self.connected_s
+    )
+  }
+}
+
+/// Per-client metrics for every client connected to a brokenithm server.
+pub struct BrokenithmMetrics {
+  clients: Mutex<HashMap<SocketAddr, ClientMetrics>>,
+}
+
+impl BrokenithmMetrics {
+  pub fn new() -> Self {
+    Self {
+      clients: Mutex::new(HashMap::new()),
+    }
+  }
+
+  pub fn connect(&self, addr: &SocketAddr) {
+    let mut clients_handle = self.clients.lock();
+    clients_handle.insert(*addr, ClientMetrics::new());
+  }
+
+  /// Removes a client and logs its final metrics.
+  pub fn disconnect(&self, addr: &SocketAddr) {
+    let mut clients_handle = self.clients.lock();
+    if let Some(client) = clients_handle.remove(addr) {
+      info!("Brokenithm client disconnected {}", client.status(addr));
+    }
+  }
+
+  /// Whether any client other than `addr` is connected.
+  pub fn has_other_clients(&self, addr: &SocketAddr) -> bool {
+    let clients_handle = self.clients.lock();
+    clients_handle.keys().any(|x| x != addr)
+  }
+
+  pub fn record_frame(&self, addr: &SocketAddr) {
+    let mut clients_handle = self.clients.lock();
+    if let Some(client) = clients_handle.get_mut(addr) {
+      let now = Instant::now();
+      client.frames += 1;
+      client.fps_frames += 1;
+      client.last_frame = Some(now);
+      client.update_fps(now);
+    }
+  }
+
+  pub fn record_malformed(&self, addr: &SocketAddr) {
+    let mut clients_handle = self.clients.lock();
+    if let Some(client) = clients_handle.get_mut(addr) {
+      client.malformed += 1;
+    }
+  }
+
+  /// Registers an outgoing ping and returns the payload to send with it. A
+  /// previous ping that is still unanswered is counted as lost.
+  pub fn record_ping(&self, addr: &SocketAddr) -> Vec<u8> {
+    let mut clients_handle = self.clients.lock();
+    match clients_handle.get_mut(addr) {
+      Some(client) => {
+        if client.pending_ping.is_some() {
+          client.lost_pings += 1;
+        }
+        client.pings += 1;
+        client.pending_ping = Some((client.pings, Instant::now()));
+        client.update_fps(Instant::now());
+        client.pings.to_be_bytes().to_vec()
+      }
+      None => vec![],
+    }
+  }
+
+  pub fn record_pong(&self, addr: &SocketAddr, payload: &[u8]) {
+    let mut clients_handle = self.clients.lock();
+    if let Some(client) = clients_handle.get_mut(addr) {
+      if let Some((seq, sent)) = client.pending_ping {
+        if payload == seq.to_be_bytes() {
+          client.rtt = Some(sent.elapsed());
+          client.pending_ping = None;
+        }
+      }
+    }
+  }
+
+  pub fn status(&self) -> Vec<BrokenithmClientStatus> {
+    let clients_handle = self.clients.lock();
+    let mut status: Vec<BrokenithmClientStatus> = clients_handle
+      .iter()
+      .map(|(addr, client)| client.status(addr))
+      .collect();
+    status.sort_by(|a, b| a.addr.cmp(&b.addr));
+
+    status
+  }
+}
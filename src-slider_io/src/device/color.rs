@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+use crate::state::SliderLights;
+
+use super::config::ColorCorrection;
+
+/// How often dithered lights are sent again when the lights have not changed.
+const DITHER_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Applies brightness, gain and gamma to lights before they are packed for a
+/// controller. With dithering, the rounding error of every channel is carried
+/// over to the next frame so that colours between the controller's levels
+/// average out over time instead of being truncated.
+pub struct ColorPipeline {
+  depth: [u8; 3],
+  dither: bool,
+  /// Corrected level from 0 to 1 for each channel value.
+  table: [[f32; 256]; 3],
+  /// Rounding error carried over for ground, left air then right air.
+  error: [f32; 3 * (31 + 3 + 3)],
+  last: Instant,
+  lights: SliderLights,
+}
+
+/// Scales a `bits` wide level up to 8 bits. The level is repeated into the low
+/// bits so that the top `bits` bits read back as `q` and full levels stay at
+/// 255, for any depth from 1 to 8 bits.
+fn expand_level(q: u8, bits: u8) -> u8 {
+  let mut out = 0;
+  let mut shift = 8 - bits as i8;
+  while shift > -(bits as i8) {
+    out |= match shift >= 0 {
+      true => q << shift,
+      false => q >> -shift,
+    };
+    shift -= bits as i8;
+  }
+  out
+}
+
+impl ColorPipeline {
+  pub fn new(correction: &ColorCorrection, depth: [u8; 3]) -> Self {
+    let mut table = [[0.0; 256]; 3];
+    for (ch, channel_table) in table.iter_mut().enumerate() {
+      for (x, level) in channel_table.iter_mut().enumerate() {
+        *level =
+          ((x as f32 / 255.0).powf(correction.gamma) * correction.brightness * correction.gain[ch])
+            .clamp(0.0, 1.0);
+      }
+    }
+
+    // Depths outside 1-8 bits cannot be expanded to a byte
+    let depth = depth.map(|x| x.clamp(1, 8));
+
+    Self {
+      depth,
+      dither: correction.dither && depth.iter().any(|x| *x < 8),
+      table,
+      error: [0.0; 3 * (31 + 3 + 3)],
+      last: Instant::now(),
+      lights: SliderLights::new(),
+    }
+  }
+
+  /// Whether unchanged lights should be sent again to keep dithering going.
+  pub fn needs_refresh(&self) -> bool {
+    self.dither && self.last.elapsed() >= DITHER_INTERVAL
+  }
+
+  fn correct(&mut self, src: &[u8], offset: usize, dst: &mut [u8]) {
+    for (i, (x, out)) in src.iter().zip(dst.iter_mut()).enumerate() {
+      let ch = i % 3;
+      let level = self.table[ch][*x as usize];
+      let bits = self.depth[ch];
+
+      *out = match self.dither && bits < 8 {
+        false => (level * 255.0).round() as u8,
+        true => {
+          let max = ((1 << bits) - 1) as f32;
+          let error = &mut self.error[offset + i];
+          let scaled = level * max + *error;
+          let q = scaled.round().clamp(0.0, max);
+          *error = scaled - q;
+
+          expand_level(q as u8, bits)
+        }
+      };
+    }
+  }
+
+  /// Returns corrected lights ready to be packed for the controller.
+  pub fn apply(&mut self, lights: &SliderLights) -> &SliderLights {
+    self.last = Instant::now();
+
+    let mut ground = [0; 3 * 31];
+    let mut air_left = [0; 3 * 3];
+    let mut air_right = [0; 3 * 3];
+    self.correct(&lights.ground, 0, &mut ground);
+    self.correct(&lights.air_left, 3 * 31, &mut air_left);
+    self.correct(&lights.air_right, 3 * 34, &mut air_right);
+
+    self.lights.ground = ground;
+    self.lights.air_left = air_left;
+    self.lights.air_right = air_right;
+    &self.lights
+  }
+}
@@ -1,4 +1,7 @@
-use serde_json::Value;
+use serde_json::{json, Value};
+use std::net::IpAddr;
+
+use crate::shared::{net::parse_bind_addrs, remote::RemoteTransport};
 
 #[derive(Debug, Clone)]
 pub enum HardwareSpec {
@@ -10,11 +13,165 @@ pub enum HardwareSpec {
   YubideckThree,
 }
 
+impl HardwareSpec {
+  /// Bits per red, green and blue channel the controller actually displays.
+  pub fn led_depth(&self) -> [u8; 3] {
+    match self {
+      HardwareSpec::Yuancon => [6, 5, 5],
+      HardwareSpec::Yubideck => [4, 4, 4],
+      _ => [8, 8, 8],
+    }
+  }
+}
+
+/// Colour correction applied to lights before they are packed for a hardware
+/// controller.
+#[derive(Debug, Clone)]
+pub struct ColorCorrection {
+  /// Brightness from 0 to 1.
+  pub brightness: f32,
+  /// Red, green and blue gain from 0 to 2.
+  pub gain: [f32; 3],
+  /// Exponent applied to each channel, 1 leaves colours unchanged.
+  pub gamma: f32,
+  /// Dithers colours over time on controllers with fewer than 8 bits per
+  /// channel.
+  pub dither: bool,
+}
+
+impl ColorCorrection {
+  pub fn default() -> Self {
+    Self {
+      brightness: 1.0,
+      gain: [1.0; 3],
+      gamma: 1.0,
+      dither: false,
+    }
+  }
+
+  pub fn from_serde_value(v: &Value) -> Option<Self> {
+    let default = Self::default();
+    v.as_object()?;
+
+    let percent = |x: &Value, default: f32, max: f64| -> f32 {
+      x.as_f64()
+        .map(|x| (x.clamp(0.0, max) / 100.0) as f32)
+        .unwrap_or(default)
+    };
+
+    Some(Self {
+      brightness: percent(&v["brightness"], default.brightness, 100.0),
+      gain: [
+        percent(&v["gainRed"], default.gain[0], 200.0),
+        percent(&v["gainGreen"], default.gain[1], 200.0),
+        percent(&v["gainBlue"], default.gain[2], 200.0),
+      ],
+      gamma: v["gamma"]
+        .as_f64()
+        .map(|x| x.clamp(0.1, 5.0) as f32)
+        .unwrap_or(default.gamma),
+      dither: v["dither"].as_bool().unwrap_or(default.dither),
+    })
+  }
+
+  pub fn from_serde_value_or_default(v: &Value) -> Self {
+    Self::from_serde_value(v).unwrap_or(Self::default())
+  }
+}
+
 #[derive(Debug, Clone)]
 pub enum BrokenithmSpec {
   Basic,
   GroundOnly,
   Nostalgia,
+  Custom(String),
+}
+
+/// Appearance options for the brokenithm web controller, served to clients as
+/// `config.js`.
+#[derive(Debug, Clone)]
+pub struct BrokenithmConfig {
+  pub invert: bool,
+  pub bg_color: String,
+  pub bg_image: Option<String>,
+  pub key_color: String,
+  pub lkey_color: String,
+  pub led_opacity: f64,
+  pub key_height: f64,
+}
+
+impl BrokenithmConfig {
+  pub fn default() -> Self {
+    Self {
+      invert: false,
+      bg_color: "#000000".to_string(),
+      bg_image: None,
+      key_color: "#FF00FF".to_string(),
+      lkey_color: "#00FFFF".to_string(),
+      led_opacity: 1.0,
+      key_height: 1.0,
+    }
+  }
+
+  pub fn from_serde_value(v: &Value) -> Option<Self> {
+    let default = Self::default();
+    v.as_object()?;
+
+    Some(Self {
+      invert: v["invert"].as_bool().unwrap_or(default.invert),
+      bg_color: v["bgColor"]
+        .as_str()
+        .map(|x| x.to_string())
+        .unwrap_or(default.bg_color),
+      bg_image: v["bgImage"].as_str().map(|x| x.to_string()),
+      key_color: v["keyColor"]
+        .as_str()
+        .map(|x| x.to_string())
+        .unwrap_or(default.key_color),
+      lkey_color: v["lkeyColor"]
+        .as_str()
+        .map(|x| x.to_string())
+        .unwrap_or(default.lkey_color),
+      led_opacity: v["ledOpacity"]
+        .as_f64()
+        .unwrap_or(default.led_opacity)
+        .clamp(0.0, 1.0),
+      key_height: v["keyHeight"]
+        .as_f64()
+        .unwrap_or(default.key_height)
+        .max(0.0),
+    })
+  }
+
+  pub fn from_serde_value_or_default(v: &Value) -> Self {
+    Self::from_serde_value(v).unwrap_or(Self::default())
+  }
+
+  pub fn from_str(s: &str) -> Option<Self> {
+    let v: Value = serde_json::from_str(s).ok()?;
+    Self::from_serde_value(&v)
+  }
+
+  pub fn to_serde_value(&self) -> Value {
+    json!({
+      "invert": self.invert,
+      "bgColor": self.bg_color,
+      "bgImage": match &self.bg_image {
+        Some(bg_image) => Value::String(bg_image.clone()),
+        None => Value::Bool(false),
+      },
+      "keyColor": self.key_color,
+      "lkeyColor": self.lkey_color,
+      "ledOpacity": self.led_opacity,
+      "keyHeight": self.key_height,
+    })
+  }
+
+  /// Renders the options as the `config.js` script expected by the web
+  /// controller.
+  pub fn to_js(&self) -> String {
+    format!("var config = {};\n", self.to_serde_value())
+  }
 }
 
 #[derive(Debug, Clone)]
@@ -23,45 +180,77 @@ pub enum DeviceMode {
   Hardware {
     spec: HardwareSpec,
     disable_air: bool,
+    correction: ColorCorrection,
   },
   Brokenithm {
     spec: BrokenithmSpec,
     lights_enabled: bool,
+    timeout_ms: u64,
     port: u16,
+    qr_direct: bool,
+    bind: Vec<IpAddr>,
   },
   DivaSlider {
     port: String,
     brightness: u8,
   },
+  Network {
+    transport: RemoteTransport,
+    port: u16,
+    bind: Vec<IpAddr>,
+  },
+  Keyboard {
+    device: i32,
+    layout: String,
+  },
+  Gamepad {
+    device: String,
+    layout: String,
+  },
+  Midi {
+    port: String,
+    layout: String,
+  },
 }
 
 impl DeviceMode {
   pub fn from_serde_value(v: &Value) -> Option<Self> {
+    // Colour correction is kept per controller type so that switching
+    // controllers does not carry over settings tuned for another one
+    let correction =
+      |mode: &str| ColorCorrection::from_serde_value_or_default(&v["ledCorrection"][mode]);
+
     Some(match v["deviceMode"].as_str()? {
       "none" => DeviceMode::None,
       "tasoller-one" => DeviceMode::Hardware {
         spec: HardwareSpec::TasollerOne,
         disable_air: v["disableAirStrings"].as_bool()?,
+        correction: correction("tasoller-one"),
       },
       "tasoller-two" => DeviceMode::Hardware {
         spec: HardwareSpec::TasollerTwo,
         disable_air: v["disableAirStrings"].as_bool()?,
+        correction: correction("tasoller-two"),
       },
       "yuancon" => DeviceMode::Hardware {
         spec: HardwareSpec::Yuancon,
         disable_air: v["disableAirStrings"].as_bool()?,
+        correction: correction("yuancon"),
       },
       "yuancon-three" => DeviceMode::Hardware {
         spec: HardwareSpec::YuanconThree,
         disable_air: v["disableAirStrings"].as_bool()?,
+        correction: correction("yuancon-three"),
       },
       "yubideck" => DeviceMode::Hardware {
         spec: HardwareSpec::Yubideck,
         disable_air: v["disableAirStrings"].as_bool()?,
+        correction: correction("yubideck"),
       },
       "yubideck-three" => DeviceMode::Hardware {
         spec: HardwareSpec::YubideckThree,
         disable_air: v["disableAirStrings"].as_bool()?,
+        correction: correction("yubideck-three"),
       },
       "diva" => DeviceMode::DivaSlider {
         port: v["divaSerialPort"].as_str()?.to_string(),
@@ -73,9 +262,12 @@ impl DeviceMode {
           true => BrokenithmSpec::GroundOnly,
         },
         lights_enabled: false,
+        timeout_ms: v["brokenithmTimeout"].as_u64().unwrap_or(5000),
         port: u16::try_from(v["brokenithmPort"].as_i64()?)
           .ok()
           .or(Some(1606))?,
+        qr_direct: v["brokenithmQrDirect"].as_bool().unwrap_or(false),
+        bind: parse_bind_addrs(v["brokenithmBindAddrs"].as_str().unwrap_or("0.0.0.0")),
       },
       "brokenithm-led" => DeviceMode::Brokenithm {
         spec: match v["disableAirStrings"].as_bool()? {
@@ -83,16 +275,74 @@ impl DeviceMode {
           true => BrokenithmSpec::GroundOnly,
         },
         lights_enabled: true,
+        timeout_ms: v["brokenithmTimeout"].as_u64().unwrap_or(5000),
         port: u16::try_from(v["brokenithmPort"].as_i64()?)
           .ok()
           .or(Some(1606))?,
+        qr_direct: v["brokenithmQrDirect"].as_bool().unwrap_or(false),
+        bind: parse_bind_addrs(v["brokenithmBindAddrs"].as_str().unwrap_or("0.0.0.0")),
       },
       "brokenithm-nostalgia" => DeviceMode::Brokenithm {
         spec: BrokenithmSpec::Nostalgia,
         lights_enabled: false,
+        timeout_ms: v["brokenithmTimeout"].as_u64().unwrap_or(5000),
         port: u16::try_from(v["brokenithmPort"].as_i64()?)
           .ok()
           .or(Some(1606))?,
+        qr_direct: v["brokenithmQrDirect"].as_bool().unwrap_or(false),
+        bind: parse_bind_addrs(v["brokenithmBindAddrs"].as_str().unwrap_or("0.0.0.0")),
+      },
+      "brokenithm-custom" => DeviceMode::Brokenithm {
+        spec: BrokenithmSpec::Custom(v["brokenithmCustomLayout"].as_str()?.to_string()),
+        lights_enabled: false,
+        timeout_ms: v["brokenithmTimeout"].as_u64().unwrap_or(5000),
+        port: u16::try_from(v["brokenithmPort"].as_i64()?)
+          .ok()
+          .or(Some(1606))?,
+        qr_direct: v["brokenithmQrDirect"].as_bool().unwrap_or(false),
+        bind: parse_bind_addrs(v["brokenithmBindAddrs"].as_str().unwrap_or("0.0.0.0")),
+      },
+      "brokenithm-custom-led" => DeviceMode::Brokenithm {
+        spec: BrokenithmSpec::Custom(v["brokenithmCustomLayout"].as_str()?.to_string()),
+        lights_enabled: true,
+        timeout_ms: v["brokenithmTimeout"].as_u64().unwrap_or(5000),
+        port: u16::try_from(v["brokenithmPort"].as_i64()?)
+          .ok()
+          .or(Some(1606))?,
+        qr_direct: v["brokenithmQrDirect"].as_bool().unwrap_or(false),
+        bind: parse_bind_addrs(v["brokenithmBindAddrs"].as_str().unwrap_or("0.0.0.0")),
+      },
+      "network-udp" => DeviceMode::Network {
+        transport: RemoteTransport::Udp,
+        port: u16::try_from(v["networkPort"].as_i64()?)
+          .ok()
+          .or(Some(1607))?,
+        bind: parse_bind_addrs(v["networkBindAddrs"].as_str().unwrap_or("0.0.0.0")),
+      },
+      "network-tcp" => DeviceMode::Network {
+        transport: RemoteTransport::Tcp,
+        port: u16::try_from(v["networkPort"].as_i64()?)
+          .ok()
+          .or(Some(1607))?,
+        bind: parse_bind_addrs(v["networkBindAddrs"].as_str().unwrap_or("0.0.0.0")),
+      },
+      "keyboard" => DeviceMode::Keyboard {
+        device: i32::try_from(v["keyboardDevice"].as_i64().unwrap_or(0)).ok()?,
+        layout: v["keyboardDeviceLayout"]
+          .as_str()
+          .unwrap_or("default")
+          .to_string(),
+      },
+      "gamepad" => DeviceMode::Gamepad {
+        device: v["gamepadDevice"].as_str().unwrap_or("").to_string(),
+        layout: v["gamepadDeviceLayout"]
+          .as_str()
+          .unwrap_or("default")
+          .to_string(),
+      },
+      "midi" => DeviceMode::Midi {
+        port: v["midiInPort"].as_str().unwrap_or("").to_string(),
+        layout: v["midiInLayout"].as_str().unwrap_or("default").to_string(),
       },
       _ => return None,
     })
@@ -104,4 +354,18 @@ impl DeviceMode {
       _ => None,
     }
   }
+
+  pub fn get_qr_direct(&self) -> bool {
+    match self {
+      DeviceMode::Brokenithm { qr_direct, .. } => *qr_direct,
+      _ => false,
+    }
+  }
+
+  pub fn get_bind(&self) -> Vec<IpAddr> {
+    match self {
+      DeviceMode::Brokenithm { bind, .. } => bind.clone(),
+      _ => vec![],
+    }
+  }
 }
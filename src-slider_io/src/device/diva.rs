@@ -316,7 +316,7 @@ impl ThreadJob for DivaSliderJob {
             for (buf_chunk, state_chunk) in lights_buf[1..94]
               .chunks_mut(3)
               .take(31)
-              .zip(lights_handle.ground.chunks(3).rev())
+              .zip(lights_handle.output().ground.chunks(3).rev())
             {
               buf_chunk[0] = state_chunk[2];
               buf_chunk[1] = state_chunk[0];
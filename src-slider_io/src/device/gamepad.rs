@@ -0,0 +1,434 @@
+use log::info;
+use rusb::{DeviceHandle, Direction, GlobalContext, TransferType};
+use std::{
+  collections::{HashMap, HashSet},
+  thread,
+  time::{Duration, Instant},
+};
+
+use crate::{
+  shared::{utils::ErrorLog, worker::ThreadJob},
+  state::SliderState,
+  system,
+};
+
+use super::{
+  hid_report::{HidAxis, HidReport, HidUsage},
+  input_map::InputMap,
+};
+
+const CLASS_HID: u8 = 0x03;
+const TIMEOUT: Duration = Duration::from_millis(20);
+
+/// The device is read over raw USB, which on Windows only works once the
+/// controller's HID driver has been replaced.
+const DRIVER_HINT: &str =
+  "on Windows the controller's driver must first be replaced with WinUSB (e.g. with Zadig)";
+
+/// How long to wait before reading again after a read error, such as the
+/// controller being unplugged.
+const ERROR_BACKOFF: Duration = Duration::from_millis(1000);
+
+/// Knob and spinner controls stay active this long after the axis last moved.
+const TURN_HOLD: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HatDirection {
+  Up,
+  Right,
+  Down,
+  Left,
+}
+
+/// Gamepad controls that can be mapped in a layout file:
+/// - `button1`, `button2`, ... for buttons
+/// - `x+`, `x-` for an axis pushed past halfway to either end
+/// - `x>`, `x<` for an axis increasing or decreasing, for knobs and spinners
+/// - `hat-up`, `hat-right`, `hat-down`, `hat-left` for the hat switch
+///
+/// Axes are `x`, `y`, `z`, `rx`, `ry`, `rz`, `slider`, `dial` and `wheel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadControl {
+  Button(u16),
+  AxisHigh(HidAxis),
+  AxisLow(HidAxis),
+  TurnUp(HidAxis),
+  TurnDown(HidAxis),
+  Hat(HatDirection),
+}
+
+impl GamepadControl {
+  pub fn from_name(name: &str) -> Option<Self> {
+    if let Some(n) = name.strip_prefix("button") {
+      return n.parse().ok().map(GamepadControl::Button);
+    }
+    if let Some(dir) = name.strip_prefix("hat-") {
+      return Some(GamepadControl::Hat(match dir {
+        "up" => HatDirection::Up,
+        "right" => HatDirection::Right,
+        "down" => HatDirection::Down,
+        "left" => HatDirection::Left,
+        _ => return None,
+      }));
+    }
+
+    let (axis, suffix) = name.split_at(name.len().checked_sub(1)?);
+    let axis = HidAxis::from_name(axis)?;
+    Some(match suffix {
+      "+" => GamepadControl::AxisHigh(axis),
+      "-" => GamepadControl::AxisLow(axis),
+      ">" => GamepadControl::TurnUp(axis),
+      "<" => GamepadControl::TurnDown(axis),
+      _ => return None,
+    })
+  }
+}
+
+pub type GamepadMap = InputMap<GamepadControl>;
+
+/// One column per button.
+const DEFAULT_GAMEPAD_MAP: &str = r#"{
+  "ground": {
+    "button1": [0, 1], "button2": [2, 3], "button3": [4, 5], "button4": [6, 7],
+    "button5": [8, 9], "button6": [10, 11], "button7": [12, 13], "button8": [14, 15],
+    "button9": [16, 17], "button10": [18, 19], "button11": [20, 21], "button12": [22, 23],
+    "button13": [24, 25], "button14": [26, 27], "button15": [28, 29], "button16": [30, 31]
+  },
+  "air": {
+    "hat-up": [0, 1], "hat-down": [4, 5]
+  }
+}"#;
+
+/// Common SDVX controller layout, BT-A to BT-D on buttons 1-4, FX-L and FX-R
+/// on 5-6, start on 7 and the knobs on the x and y axes. Matches the pads used
+/// by the Voltex keyboard and lighting layouts.
+const VOLTEX_GAMEPAD_MAP: &str = r#"{
+  "ground": {
+    "button1": [9, 11], "button2": [13, 15], "button3": [17, 19], "button4": [21, 23],
+    "button5": [8, 10, 12, 14], "button6": [16, 18, 20, 22],
+    "x<": [0, 1, 2, 3], "x>": [4, 5, 6, 7],
+    "y<": [24, 25, 26, 27], "y>": [28, 29, 30, 31]
+  },
+  "extra": {
+    "button7": 1
+  }
+}"#;
+
+fn load_gamepad_map(name: &str) -> Result<GamepadMap, String> {
+  GamepadMap::load(
+    system::get_gamepad_layout_dir(),
+    name,
+    &[
+      ("default", DEFAULT_GAMEPAD_MAP),
+      ("voltex", VOLTEX_GAMEPAD_MAP),
+    ],
+    GamepadControl::from_name,
+  )
+}
+
+fn parse_vid_pid(device: &str) -> Option<(u16, u16)> {
+  let (vid, pid) = device.trim().split_once(':')?;
+  Some((
+    u16::from_str_radix(vid, 16).ok()?,
+    u16::from_str_radix(pid, 16).ok()?,
+  ))
+}
+
+/// Reads a generic USB HID gamepad or joystick. The report descriptor of the
+/// device is used to find its buttons and axes, which are then mapped to slider
+/// inputs through a layout file.
+pub struct GamepadJob {
+  state: SliderState,
+  device: String,
+  layout: String,
+  errors: ErrorLog,
+
+  gamepad_map: Option<GamepadMap>,
+  handle: Option<DeviceHandle<GlobalContext>>,
+  interface: u8,
+  endpoint: u8,
+  report: Option<HidReport>,
+  read_buf: Vec<u8>,
+  /// Whether the last read failed, so that an error is only reported once.
+  read_failed: bool,
+
+  last_values: HashMap<HidUsage, i64>,
+  last_turns: HashMap<GamepadControl, Instant>,
+  active: HashSet<GamepadControl>,
+}
+
+impl GamepadJob {
+  pub fn new(state: &SliderState, device: &String, layout: &String, errors: &ErrorLog) -> Self {
+    Self {
+      state: state.clone(),
+      device: device.clone(),
+      layout: layout.clone(),
+      errors: errors.clone(),
+
+      gamepad_map: None,
+      handle: None,
+      interface: 0,
+      endpoint: 0,
+      report: None,
+      read_buf: vec![],
+      read_failed: false,
+
+      last_values: HashMap::new(),
+      last_turns: HashMap::new(),
+      active: HashSet::new(),
+    }
+  }
+
+  fn get_handle(&mut self, vid: u16, pid: u16) -> Result<(), String> {
+    // List HID devices so users can find the id of their controller
+    for device in rusb::devices().map_err(|e| e.to_string())?.iter() {
+      if let (Ok(desc), Ok(config)) = (
+        device.device_descriptor(),
+        device.active_config_descriptor(),
+      ) {
+        if config
+          .interfaces()
+          .flat_map(|x| x.descriptors())
+          .any(|x| x.class_code() == CLASS_HID)
+        {
+          info!(
+            "Gamepad found HID device {:04x}:{:04x}",
+            desc.vendor_id(),
+            desc.product_id()
+          );
+        }
+      }
+    }
+
+    let handle = rusb::open_device_with_vid_pid(vid, pid).ok_or(format!(
+      "device {:04x}:{:04x} not found or not openable, {}",
+      vid, pid, DRIVER_HINT
+    ))?;
+    info!("Gamepad device found {:?}", handle);
+
+    // First HID interface with an interrupt in endpoint
+    let config = handle
+      .device()
+      .active_config_descriptor()
+      .map_err(|e| e.to_string())?;
+    let (interface, endpoint, packet_size) = config
+      .interfaces()
+      .flat_map(|x| x.descriptors())
+      .filter(|x| x.class_code() == CLASS_HID)
+      .find_map(|x| {
+        x.endpoint_descriptors()
+          .find(|e| e.direction() == Direction::In && e.transfer_type() == TransferType::Interrupt)
+          .map(|e| (x.interface_number(), e.address(), e.max_packet_size()))
+      })
+      .ok_or("no HID input endpoint".to_string())?;
+
+    if handle.kernel_driver_active(interface).unwrap_or(false) {
+      info!("Gamepad detaching kernel driver");
+      handle
+        .detach_kernel_driver(interface)
+        .map_err(|e| e.to_string())?;
+    }
+    handle
+      .claim_interface(interface)
+      .map_err(|e| format!("{}, {}", e, DRIVER_HINT))?;
+
+    // GET_DESCRIPTOR for the HID report descriptor of the interface
+    let mut desc = [0; 4096];
+    let len = handle
+      .read_control(
+        0x81,
+        0x06,
+        0x2200,
+        interface as u16,
+        &mut desc,
+        TIMEOUT * 10,
+      )
+      .map_err(|e| format!("report descriptor unavailable: {}", e))?;
+    let report = HidReport::parse(&desc[..len]).ok_or("bad report descriptor".to_string())?;
+    for field in report.fields.iter() {
+      info!(
+        "Gamepad field {:?} range {}..{}",
+        field.usage, field.logical_min, field.logical_max
+      );
+    }
+
+    self.interface = interface;
+    self.endpoint = endpoint;
+    self.read_buf = vec![0; (packet_size as usize).max(64)];
+    self.report = Some(report);
+    self.handle = Some(handle);
+    Ok(())
+  }
+
+  /// Updates the set of active controls from an input report.
+  fn read_report(&mut self, len: usize) {
+    let report = self.report.as_ref().unwrap();
+    let now = Instant::now();
+
+    for (field, value) in report.read(&self.read_buf[..len]) {
+      // Ranges may span all of i32, so the arithmetic is done in i64
+      let value = value as i64;
+      let min = field.logical_min as i64;
+      let max = field.logical_max as i64;
+
+      match field.usage {
+        HidUsage::Button(n) => {
+          set_active(&mut self.active, GamepadControl::Button(n), value != 0);
+        }
+        HidUsage::Axis(axis) => {
+          let quarter = (max - min) / 4;
+          set_active(
+            &mut self.active,
+            GamepadControl::AxisHigh(axis),
+            value > max - quarter,
+          );
+          set_active(
+            &mut self.active,
+            GamepadControl::AxisLow(axis),
+            value < min + quarter,
+          );
+
+          // Knobs wrap around at the end of their range
+          if let Some(last) = self.last_values.insert(field.usage, value) {
+            let range = max - min + 1;
+            let mut delta = value - last;
+            if delta > range / 2 {
+              delta -= range;
+            } else if delta < -range / 2 {
+              delta += range;
+            }
+            match delta {
+              x if x > 0 => self.last_turns.insert(GamepadControl::TurnUp(axis), now),
+              x if x < 0 => self.last_turns.insert(GamepadControl::TurnDown(axis), now),
+              _ => None,
+            };
+          }
+        }
+        HidUsage::Hat => {
+          // Hat switches count clockwise from up in eighths, anything out of
+          // range is centered
+          let dir = match value - min {
+            x if x >= 0 && x < 8 && value <= max => Some(x),
+            _ => None,
+          };
+          for (hat, dirs) in [
+            (HatDirection::Up, [7, 0, 1]),
+            (HatDirection::Right, [1, 2, 3]),
+            (HatDirection::Down, [3, 4, 5]),
+            (HatDirection::Left, [5, 6, 7]),
+          ] {
+            set_active(
+              &mut self.active,
+              GamepadControl::Hat(hat),
+              dir.map(|x| dirs.contains(&x)).unwrap_or(false),
+            );
+          }
+        }
+      }
+    }
+  }
+
+  /// Releases knobs that stopped turning, returning true if any were released.
+  fn update_turns(&mut self) -> bool {
+    let mut changed = false;
+    for (control, last_turn) in self.last_turns.iter() {
+      let turning = last_turn.elapsed() < TURN_HOLD;
+      changed |= set_active(&mut self.active, *control, turning);
+    }
+    changed
+  }
+}
+
+/// Returns true if the control changed state.
+fn set_active(active: &mut HashSet<GamepadControl>, control: GamepadControl, on: bool) -> bool {
+  match on {
+    true => active.insert(control),
+    false => active.remove(&control),
+  }
+}
+
+impl ThreadJob for GamepadJob {
+  fn setup(&mut self) -> bool {
+    match load_gamepad_map(&self.layout) {
+      Ok(gamepad_map) => self.gamepad_map = Some(gamepad_map),
+      Err(e) => {
+        self.errors.push(format!(
+          "Gamepad layout {} could not be loaded: {}",
+          self.layout, e
+        ));
+        return false;
+      }
+    }
+
+    let (vid, pid) = match parse_vid_pid(&self.device) {
+      Some(x) => x,
+      None => {
+        self.errors.push(format!(
+          "Gamepad device \"{}\" should be a hex VID:PID such as 1ccf:101c",
+          self.device
+        ));
+        return false;
+      }
+    };
+
+    match self.get_handle(vid, pid) {
+      Ok(_) => {
+        info!("Gamepad OK");
+        true
+      }
+      Err(e) => {
+        self.errors.push(format!("Gamepad setup failed: {}", e));
+        false
+      }
+    }
+  }
+
+  fn tick(&mut self) -> bool {
+    let handle = self.handle.as_ref().unwrap();
+    let mut work = false;
+
+    match handle.read_interrupt(self.endpoint, &mut self.read_buf, TIMEOUT) {
+      Ok(len) => {
+        self.read_failed = false;
+        if len > 0 {
+          self.read_report(len);
+          work = true;
+        }
+      }
+      // Timeouts just mean nothing changed
+      Err(rusb::Error::Timeout) => {}
+      Err(e) => {
+        if !self.read_failed {
+          self.errors.push(format!("Gamepad read failed: {}", e));
+          self.read_failed = true;
+        }
+        thread::sleep(ERROR_BACKOFF);
+      }
+    }
+    work |= self.update_turns();
+
+    if work {
+      let mut input_handle = self.state.input.lock();
+      self
+        .gamepad_map
+        .as_ref()
+        .unwrap()
+        .apply(self.active.iter(), &mut input_handle);
+    }
+
+    work
+  }
+}
+
+impl Drop for GamepadJob {
+  fn drop(&mut self) {
+    if let Some(handle) = self.handle.as_mut() {
+      handle.release_interface(self.interface).ok();
+    }
+
+    let mut input_handle = self.state.input.lock();
+    input_handle.ground.fill(0);
+    input_handle.air.fill(0);
+    input_handle.extra.fill(0);
+  }
+}
@@ -3,7 +3,7 @@ use rusb::{self, DeviceHandle, GlobalContext};
 use std::{
   error::Error,
   mem::swap,
-  ops::{Deref, DerefMut},
+  ops::DerefMut,
   time::Duration,
 };
 
@@ -15,7 +15,10 @@ use crate::{
   state::{SliderInput, SliderLights, SliderState},
 };
 
-use super::config::HardwareSpec;
+use super::{
+  color::ColorPipeline,
+  config::{ColorCorrection, HardwareSpec},
+};
 
 type HidReadCallback = fn(&Buffer, &mut SliderInput) -> ();
 type HidLedCallback = fn(&mut Buffer, &mut Buffer, &SliderLights) -> ();
@@ -42,6 +45,7 @@ pub struct HidJob {
   led_callback: HidLedCallback,
   led_buf: Buffer,
   led_buf_two: Buffer,
+  color: ColorPipeline,
 
   handle: Option<DeviceHandle<GlobalContext>>,
 }
@@ -57,6 +61,7 @@ impl HidJob {
     read_callback: HidReadCallback,
     led_type: WriteType,
     led_callback: HidLedCallback,
+    color: ColorPipeline,
   ) -> Self {
     Self {
       state,
@@ -72,11 +77,18 @@ impl HidJob {
       led_callback,
       led_buf: Buffer::new(),
       led_buf_two: Buffer::new(),
+      color,
       handle: None,
     }
   }
 
-  pub fn from_config(state: &SliderState, spec: &HardwareSpec, disable_air: &bool) -> Self {
+  pub fn from_config(
+    state: &SliderState,
+    spec: &HardwareSpec,
+    disable_air: &bool,
+    correction: &ColorCorrection,
+  ) -> Self {
+    let color = ColorPipeline::new(correction, spec.led_depth());
     match spec {
       HardwareSpec::TasollerOne => Self::new(
         state.clone(),
@@ -120,6 +132,7 @@ impl HidJob {
           }
           buf.data[96..240].fill(0);
         },
+        color,
       ),
       HardwareSpec::TasollerTwo => Self::new(
         state.clone(),
@@ -170,6 +183,7 @@ impl HidJob {
             }
           }
         },
+        color,
       ),
       HardwareSpec::Yuancon => Self::new(
         state.clone(),
@@ -204,6 +218,7 @@ impl HidJob {
             buf_chunk[1] = (state_chunk[1] & 0xf8) | (state_chunk[0] >> 5);
           }
         },
+        color,
       ),
       HardwareSpec::YuanconThree => Self::new(
         state.clone(),
@@ -251,6 +266,7 @@ impl HidJob {
             buf_chunk[2] = state_chunk[2];
           }
         },
+        color,
       ),
       HardwareSpec::Yubideck => Self::new(
         state.clone(),
@@ -300,6 +316,7 @@ impl HidJob {
             buf_chunk[2] = (state_chunk[4]) | (state_chunk[5] << 4);
           }
         },
+        color,
       ),
       HardwareSpec::YubideckThree => Self::new(
         state.clone(),
@@ -350,6 +367,7 @@ impl HidJob {
           buf_two.data[34..37].copy_from_slice(&lights.air_left[3..6]);
           buf_two.data[37..40].copy_from_slice(&lights.air_right[3..6]);
         },
+        color,
       ),
     }
   }
@@ -432,12 +450,9 @@ impl ThreadJob for HidJob {
     {
       {
         let mut lights_handle = self.state.lights.lock();
-        if lights_handle.dirty {
-          (self.led_callback)(
-            &mut self.led_buf,
-            &mut self.led_buf_two,
-            lights_handle.deref(),
-          );
+        if lights_handle.dirty || self.color.needs_refresh() {
+          let lights = self.color.apply(&lights_handle.output());
+          (self.led_callback)(&mut self.led_buf, &mut self.led_buf_two, lights);
           lights_handle.dirty = false;
         }
       }
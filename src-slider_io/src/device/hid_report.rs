@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+
+// Minimal HID report descriptor parser, only keeps the input fields a gamepad
+// or joystick uses: buttons, the generic desktop axes and hat switches.
+
+const PAGE_GENERIC_DESKTOP: u32 = 0x01;
+const PAGE_BUTTON: u32 = 0x09;
+const USAGE_HAT_SWITCH: u32 = 0x39;
+
+/// Longest input report accepted, the largest interrupt packet USB allows.
+/// Descriptors with fields past it are rejected, as the report count comes
+/// straight from the device.
+const MAX_REPORT_LEN: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HidAxis {
+  X,
+  Y,
+  Z,
+  Rx,
+  Ry,
+  Rz,
+  Slider,
+  Dial,
+  Wheel,
+}
+
+impl HidAxis {
+  fn from_usage(usage: u32) -> Option<Self> {
+    Some(match usage {
+      0x30 => HidAxis::X,
+      0x31 => HidAxis::Y,
+      0x32 => HidAxis::Z,
+      0x33 => HidAxis::Rx,
+      0x34 => HidAxis::Ry,
+      0x35 => HidAxis::Rz,
+      0x36 => HidAxis::Slider,
+      0x37 => HidAxis::Dial,
+      0x38 => HidAxis::Wheel,
+      _ => return None,
+    })
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    Some(match name {
+      "x" => HidAxis::X,
+      "y" => HidAxis::Y,
+      "z" => HidAxis::Z,
+      "rx" => HidAxis::Rx,
+      "ry" => HidAxis::Ry,
+      "rz" => HidAxis::Rz,
+      "slider" => HidAxis::Slider,
+      "dial" => HidAxis::Dial,
+      "wheel" => HidAxis::Wheel,
+      _ => return None,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HidUsage {
+  /// Buttons are numbered from 1.
+  Button(u16),
+  Axis(HidAxis),
+  Hat,
+}
+
+#[derive(Debug, Clone)]
+pub struct HidField {
+  pub usage: HidUsage,
+  pub report_id: u8,
+  pub bit_offset: usize,
+  pub bit_size: usize,
+  pub logical_min: i32,
+  pub logical_max: i32,
+}
+
+impl HidField {
+  fn read(&self, data: &[u8]) -> Option<i32> {
+    if self.bit_size == 0 || self.bit_size > 32 || self.bit_offset + self.bit_size > data.len() * 8
+    {
+      return None;
+    }
+
+    let mut value: u32 = 0;
+    for i in 0..self.bit_size {
+      let bit = self.bit_offset + i;
+      if data[bit / 8] & (1 << (bit % 8)) != 0 {
+        value |= 1 << i;
+      }
+    }
+
+    // Sign extend fields with a negative logical range
+    if self.logical_min < 0 && self.bit_size < 32 && value & (1 << (self.bit_size - 1)) != 0 {
+      value |= u32::MAX << self.bit_size;
+    }
+
+    Some(value as i32)
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Globals {
+  usage_page: u32,
+  logical_min: i32,
+  logical_max: i32,
+  report_size: usize,
+  report_count: usize,
+  report_id: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct HidReport {
+  pub fields: Vec<HidField>,
+  pub uses_report_ids: bool,
+}
+
+impl HidReport {
+  /// Parses the input fields out of a report descriptor.
+  pub fn parse(desc: &[u8]) -> Option<Self> {
+    let mut fields = vec![];
+    let mut uses_report_ids = false;
+    let mut globals = Globals::default();
+    let mut global_stack: Vec<Globals> = vec![];
+    let mut usages: Vec<u32> = vec![];
+    let mut usage_min: Option<u32> = None;
+    let mut usage_max: Option<u32> = None;
+    let mut offsets: HashMap<u8, usize> = HashMap::new();
+
+    let mut i = 0;
+    while i < desc.len() {
+      let prefix = desc[i];
+
+      // Long items are reserved and never used by real devices
+      if prefix == 0xfe {
+        let len = *desc.get(i + 1)? as usize;
+        i += 3 + len;
+        continue;
+      }
+
+      let size = match prefix & 0x03 {
+        3 => 4,
+        x => x as usize,
+      };
+      let data = desc.get(i + 1..i + 1 + size)?;
+      i += 1 + size;
+
+      let unsigned = data
+        .iter()
+        .rev()
+        .fold(0u32, |acc, x| (acc << 8) | *x as u32);
+      let signed = match size {
+        1 => data[0] as i8 as i32,
+        2 => i16::from_le_bytes([data[0], data[1]]) as i32,
+        4 => unsigned as i32,
+        _ => 0,
+      };
+
+      match (prefix >> 2) & 0x03 {
+        // Main items
+        0 => {
+          match prefix >> 4 {
+            // Input
+            0x8 => {
+              let offset = offsets.entry(globals.report_id).or_insert(0);
+              let constant = unsigned & 0x01 != 0;
+              let variable = unsigned & 0x02 != 0;
+
+              let end = globals
+                .report_size
+                .checked_mul(globals.report_count)
+                .and_then(|x| x.checked_add(*offset))?;
+              if end > MAX_REPORT_LEN * 8 {
+                return None;
+              }
+
+              if !constant && variable {
+                for n in 0..globals.report_count {
+                  let usage = match (usages.get(n).or(usages.last()), usage_min) {
+                    (Some(usage), None) => Some(*usage),
+                    (_, Some(min)) => match min.checked_add(n as u32) {
+                      Some(x) if usage_max.map(|max| x <= max).unwrap_or(true) => Some(x),
+                      _ => None,
+                    },
+                    _ => None,
+                  };
+                  let usage = usage.and_then(|usage| {
+                    let page = match usage >> 16 {
+                      0 => globals.usage_page,
+                      x => x,
+                    };
+                    let id = usage & 0xffff;
+                    match page {
+                      PAGE_BUTTON => Some(HidUsage::Button(id as u16)),
+                      PAGE_GENERIC_DESKTOP if id == USAGE_HAT_SWITCH => Some(HidUsage::Hat),
+                      PAGE_GENERIC_DESKTOP => HidAxis::from_usage(id).map(HidUsage::Axis),
+                      _ => None,
+                    }
+                  });
+
+                  if let Some(usage) = usage {
+                    // Some descriptors give an unsigned maximum that only
+                    // fits when read as unsigned
+                    let logical_max = match globals.logical_max < globals.logical_min {
+                      true => (globals.logical_max as u32 & field_mask(globals.report_size)) as i32,
+                      false => globals.logical_max,
+                    };
+                    fields.push(HidField {
+                      usage,
+                      report_id: globals.report_id,
+                      bit_offset: *offset + n * globals.report_size,
+                      bit_size: globals.report_size,
+                      logical_min: globals.logical_min,
+                      logical_max,
+                    });
+                  }
+                }
+              }
+
+              *offset = end;
+            }
+            // Output, feature, collection and end collection
+            _ => {}
+          }
+          usages.clear();
+          usage_min = None;
+          usage_max = None;
+        }
+        // Global items
+        1 => match prefix >> 4 {
+          0x0 => globals.usage_page = unsigned,
+          0x1 => globals.logical_min = signed,
+          0x2 => globals.logical_max = signed,
+          0x7 => globals.report_size = unsigned as usize,
+          0x8 => {
+            globals.report_id = unsigned as u8;
+            uses_report_ids = true;
+          }
+          0x9 => globals.report_count = unsigned as usize,
+          0xa => global_stack.push(globals.clone()),
+          0xb => globals = global_stack.pop()?,
+          _ => {}
+        },
+        // Local items, 4 byte usages include their own usage page
+        2 => match prefix >> 4 {
+          0x0 => usages.push(unsigned),
+          0x1 => usage_min = Some(unsigned),
+          0x2 => usage_max = Some(unsigned),
+          _ => {}
+        },
+        _ => {}
+      }
+    }
+
+    Some(Self {
+      fields,
+      uses_report_ids,
+    })
+  }
+
+  /// Reads every known field out of an input report, as sent by the device.
+  pub fn read<'a>(&'a self, report: &'a [u8]) -> impl Iterator<Item = (&'a HidField, i32)> + 'a {
+    let (report_id, data) = match (self.uses_report_ids, report.split_first()) {
+      (true, Some((report_id, data))) => (*report_id, data),
+      _ => (0, report),
+    };
+
+    self
+      .fields
+      .iter()
+      .filter(move |field| field.report_id == report_id)
+      .filter_map(move |field| field.read(data).map(|value| (field, value)))
+  }
+}
+
+fn field_mask(bits: usize) -> u32 {
+  match bits {
+    0 => 0,
+    x if x >= 32 => u32::MAX,
+    x => (1 << x) - 1,
+  }
+}
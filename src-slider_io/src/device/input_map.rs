@@ -0,0 +1,122 @@
+use serde_json::Value;
+use std::{collections::HashMap, fs, hash::Hash, path::PathBuf};
+
+use crate::{state::SliderInput, system};
+
+#[derive(Debug, Clone, Copy)]
+pub enum InputTarget {
+  Ground(usize),
+  Air(usize),
+  Extra(usize),
+}
+
+/// Maps named controls of an input device to slider inputs. Layout files have
+/// the form `{"ground": {"a": [0, 1]}, "air": {"b": 0}, "extra": {"c": 0}}`
+/// where names are device specific and values are one or more indices.
+pub struct InputMap<K> {
+  targets: HashMap<K, Vec<InputTarget>>,
+}
+
+impl<K: Hash + Eq> InputMap<K> {
+  /// Parses a layout, using `parse_name` to turn control names into keys.
+  pub fn from_serde_value(
+    v: &Value,
+    parse_name: impl Fn(&str) -> Option<K>,
+  ) -> Result<Self, String> {
+    let mut targets: HashMap<K, Vec<InputTarget>> = HashMap::new();
+
+    for (section, len) in [("ground", 32), ("air", 6), ("extra", 3)] {
+      let names = match &v[section] {
+        Value::Null => continue,
+        Value::Object(names) => names,
+        _ => return Err(format!("\"{}\" should be an object", section)),
+      };
+
+      for (name, idxs) in names.iter() {
+        let key = parse_name(name).ok_or(format!("unknown control \"{}\"", name))?;
+        let idxs = match idxs {
+          Value::Array(idxs) => idxs.iter().map(|x| x.as_u64()).collect(),
+          x => vec![x.as_u64()],
+        };
+
+        let entry = targets.entry(key).or_default();
+        for idx in idxs {
+          let idx = match idx {
+            Some(idx) if (idx as usize) < len => idx as usize,
+            _ => return Err(format!("bad {} index for \"{}\"", section, name)),
+          };
+          entry.push(match section {
+            "ground" => InputTarget::Ground(idx),
+            "air" => InputTarget::Air(idx),
+            _ => InputTarget::Extra(idx),
+          });
+        }
+      }
+    }
+
+    Ok(Self { targets })
+  }
+
+  /// Loads `<name>.json` from `dir`. Layouts listed in `builtin` are used if
+  /// there is no such file.
+  pub fn load(
+    dir: Option<Box<PathBuf>>,
+    name: &str,
+    builtin: &[(&str, &str)],
+    parse_name: impl Fn(&str) -> Option<K>,
+  ) -> Result<Self, String> {
+    let dir = dir.ok_or("config directory not found".to_string())?;
+    let path = system::join_config_path(&dir, &format!("{}.json", name))
+      .ok_or(format!("\"{}\" is not a layout name", name))?;
+
+    let data = match fs::read_to_string(path.as_path()) {
+      Ok(data) => data,
+      Err(e) => match builtin.iter().find(|(x, _)| *x == name) {
+        Some((_, data)) => data.to_string(),
+        None => return Err(format!("{}: {}", path.display(), e)),
+      },
+    };
+    let v: Value = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+
+    Self::from_serde_value(&v, parse_name)
+  }
+
+  /// Every mapped control and the slider inputs it is mapped to.
+  pub fn targets(&self) -> impl Iterator<Item = (&K, &Vec<InputTarget>)> {
+    self.targets.iter()
+  }
+
+  /// Sets the inputs of every active control, leaving the rest released.
+  pub fn apply<'a>(&self, active: impl Iterator<Item = &'a K>, input: &mut SliderInput)
+  where
+    K: 'a,
+  {
+    self.apply_values(active.map(|key| (key, 255)), input);
+  }
+
+  /// Like `apply`, but with a pressure value for every active control. Air and
+  /// extra inputs are pressed for any non-zero value.
+  pub fn apply_values<'a>(&self, active: impl Iterator<Item = (&'a K, u8)>, input: &mut SliderInput)
+  where
+    K: 'a,
+  {
+    input.ground.fill(0);
+    input.air.fill(0);
+    input.extra.fill(0);
+
+    for (key, value) in active {
+      if value == 0 {
+        continue;
+      }
+      if let Some(targets) = self.targets.get(key) {
+        for target in targets {
+          match *target {
+            InputTarget::Ground(idx) => input.ground[idx] = input.ground[idx].max(value),
+            InputTarget::Air(idx) => input.air[idx] = 1,
+            InputTarget::Extra(idx) => input.extra[idx] = 1,
+          }
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,246 @@
+use interception::{
+  Device, Filter, Interception, KeyFilter, KeyState, Predicate, ScanCode, Stroke,
+};
+use log::info;
+use serde_json::Value;
+use std::{collections::HashSet, time::Duration};
+
+use crate::{
+  shared::{utils::ErrorLog, worker::ThreadJob},
+  state::SliderState,
+  system,
+};
+
+use super::input_map::InputMap;
+
+/// A scan code and whether it has the E0 prefix, which tells apart keys that
+/// share a scan code such as Up and Numpad8.
+pub type KeyCode = (ScanCode, bool);
+
+/// Scan code names as used by interception, e.g. `Q`, `Num1` or `F1`, and the
+/// names in `EXTENDED_KEYS`.
+pub type KeyMap = InputMap<KeyCode>;
+
+/// Names of common E0 keys. Other E0 keys can be written as `E0+<name>`.
+const EXTENDED_KEYS: &[(&str, ScanCode)] = &[
+  ("Up", ScanCode::Numpad8),
+  ("Down", ScanCode::Numpad2),
+  ("Left", ScanCode::Numpad4),
+  ("Right", ScanCode::Numpad6),
+  ("Home", ScanCode::Numpad7),
+  ("End", ScanCode::Numpad1),
+  ("PageUp", ScanCode::Numpad9),
+  ("PageDown", ScanCode::Numpad3),
+  ("Insert", ScanCode::Numpad0),
+  ("Delete", ScanCode::NumpadPeriod),
+  ("NumpadEnter", ScanCode::Enter),
+  ("NumpadSlash", ScanCode::Slash),
+  ("RightControl", ScanCode::LeftControl),
+  ("RightAlt", ScanCode::LeftAlt),
+];
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+  if let Some((_, code)) = EXTENDED_KEYS.iter().find(|(x, _)| *x == name) {
+    return Some((*code, true));
+  }
+  let (name, e0) = match name.strip_prefix("E0+") {
+    Some(name) => (name, true),
+    None => (name, false),
+  };
+  let code = serde_json::from_value(Value::String(name.to_string())).ok()?;
+  Some((code, e0))
+}
+
+const DEFAULT_KEY_MAP: &str = r#"{
+  "ground": {
+    "Q": [0, 1], "W": [2, 3], "E": [4, 5], "R": [6, 7],
+    "T": [8, 9], "Y": [10, 11], "U": [12, 13], "I": [14, 15],
+    "A": [16, 17], "S": [18, 19], "D": [20, 21], "F": [22, 23],
+    "G": [24, 25], "H": [26, 27], "J": [28, 29], "K": [30, 31]
+  },
+  "air": {
+    "Num1": 0, "Num2": 1, "Num3": 2, "Num4": 3, "Num5": 4, "Num6": 5
+  },
+  "extra": {
+    "F1": 0, "F2": 1, "F3": 2
+  }
+}"#;
+
+/// Loads a layout from the keyboard layout directory. The `default` layout
+/// falls back to a built-in one if there is no such file.
+fn load_key_map(name: &str) -> Result<KeyMap, String> {
+  KeyMap::load(
+    system::get_keyboard_layout_dir(),
+    name,
+    &[("default", DEFAULT_KEY_MAP)],
+    parse_key,
+  )
+}
+
+/// Interception filters are chosen with a plain function, so there is one
+/// predicate per keyboard device number.
+extern "C" fn is_device<const N: Device>(device: Device) -> bool {
+  device == N
+}
+
+const DEVICE_PREDICATES: [Predicate; 10] = [
+  is_device::<1>,
+  is_device::<2>,
+  is_device::<3>,
+  is_device::<4>,
+  is_device::<5>,
+  is_device::<6>,
+  is_device::<7>,
+  is_device::<8>,
+  is_device::<9>,
+  is_device::<10>,
+];
+
+/// Reads a physical keyboard through interception and uses it as a slider.
+/// Only the chosen keyboard is filtered, so its keystrokes are swallowed while
+/// every other keyboard never passes through slidershim. The keyboard must be
+/// picked by its device number so that the main keyboard is never captured by
+/// accident.
+pub struct KeyboardJob {
+  state: SliderState,
+  device: Device,
+  layout: String,
+  errors: ErrorLog,
+
+  key_map: Option<KeyMap>,
+  interception: Option<Interception>,
+  pressed: HashSet<KeyCode>,
+  strokes: [Stroke; 32],
+}
+
+// Interception contexts are only used from the worker thread
+unsafe impl Send for KeyboardJob {}
+
+impl KeyboardJob {
+  pub fn new(state: &SliderState, device: &i32, layout: &String, errors: &ErrorLog) -> Self {
+    Self {
+      state: state.clone(),
+      device: *device,
+      layout: layout.clone(),
+      errors: errors.clone(),
+
+      key_map: None,
+      interception: None,
+      pressed: HashSet::new(),
+      strokes: [Stroke::Keyboard {
+        code: ScanCode::Esc,
+        state: KeyState::UP,
+        information: 0,
+      }; 32],
+    }
+  }
+}
+
+impl ThreadJob for KeyboardJob {
+  fn setup(&mut self) -> bool {
+    match load_key_map(&self.layout) {
+      Ok(key_map) => self.key_map = Some(key_map),
+      Err(e) => {
+        self.errors.push(format!(
+          "Keyboard layout {} could not be loaded: {}",
+          self.layout, e
+        ));
+        return false;
+      }
+    }
+
+    let interception = match Interception::new() {
+      Some(interception) => interception,
+      None => {
+        self
+          .errors
+          .push("Keyboard input cannot load interception, is the driver installed?".to_string());
+        return false;
+      }
+    };
+
+    // Hardware ids are utf-16, log them so users can tell keyboards apart
+    let mut hardware_id = [0; 512];
+    let mut devices = vec![];
+    for device in 1..=10 {
+      let len =
+        (interception.get_hardware_id(device, &mut hardware_id) as usize).min(hardware_id.len());
+      if len > 0 {
+        let id = hardware_id[..len]
+          .chunks_exact(2)
+          .map(|x| u16::from_le_bytes([x[0], x[1]]))
+          .take_while(|x| *x != 0)
+          .collect::<Vec<u16>>();
+        let id = String::from_utf16_lossy(&id);
+        info!("Keyboard device {} {}", device, id);
+        devices.push(format!("{} {}", device, id));
+      }
+    }
+
+    if !(1..=10).contains(&self.device) {
+      self.errors.push(format!(
+        "Keyboard input needs a keyboard device number, connected keyboards: {}",
+        devices.join("; ")
+      ));
+      return false;
+    }
+
+    interception.set_filter(
+      DEVICE_PREDICATES[self.device as usize - 1],
+      Filter::KeyFilter(KeyFilter::DOWN | KeyFilter::UP | KeyFilter::E0),
+    );
+    info!("Keyboard input capturing device {}", self.device);
+    self.interception = Some(interception);
+
+    true
+  }
+
+  fn tick(&mut self) -> bool {
+    let interception = self.interception.as_ref().unwrap();
+
+    let device = interception.wait_with_timeout(Duration::from_millis(10));
+    if device <= 0 {
+      return false;
+    }
+    let n = interception.receive(device, &mut self.strokes);
+    if n <= 0 {
+      return false;
+    }
+    let strokes = &self.strokes[..n as usize];
+
+    for stroke in strokes {
+      if let Stroke::Keyboard { code, state, .. } = stroke {
+        let key = (*code, state.contains(KeyState::E0));
+        match state.contains(KeyState::UP) {
+          false => self.pressed.insert(key),
+          true => self.pressed.remove(&key),
+        };
+      }
+    }
+
+    let mut input_handle = self.state.input.lock();
+    self
+      .key_map
+      .as_ref()
+      .unwrap()
+      .apply(self.pressed.iter(), &mut input_handle);
+
+    true
+  }
+}
+
+impl Drop for KeyboardJob {
+  fn drop(&mut self) {
+    if let Some(interception) = self.interception.take() {
+      interception.set_filter(
+        DEVICE_PREDICATES[self.device as usize - 1],
+        Filter::KeyFilter(KeyFilter::empty()),
+      );
+    }
+
+    let mut input_handle = self.state.input.lock();
+    input_handle.ground.fill(0);
+    input_handle.air.fill(0);
+    input_handle.extra.fill(0);
+  }
+}
@@ -0,0 +1,203 @@
+use async_trait::async_trait;
+use log::info;
+use midir::{MidiInput, MidiInputConnection};
+use std::{collections::HashMap, future::Future};
+
+use crate::{
+  shared::{utils::ErrorLog, worker::AsyncHaltableJob},
+  state::SliderState,
+  system,
+};
+
+use super::input_map::InputMap;
+
+/// MIDI controls that can be mapped in a layout file, `note36` for a note
+/// number and `cc64` for a control change number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MidiControl {
+  Note(u8),
+  Cc(u8),
+}
+
+impl MidiControl {
+  pub fn from_name(name: &str) -> Option<Self> {
+    let (control, n): (fn(u8) -> Self, &str) = match name {
+      x if x.starts_with("note") => (MidiControl::Note, &x[4..]),
+      x if x.starts_with("cc") => (MidiControl::Cc, &x[2..]),
+      _ => return None,
+    };
+    match n.parse() {
+      Ok(n) if n < 128 => Some(control(n)),
+      _ => None,
+    }
+  }
+}
+
+pub type MidiMap = InputMap<MidiControl>;
+
+/// Notes 36-51 (the usual range of 16 pad boards) as slider columns, general
+/// purpose controllers 16-21 as air strings and the sustain pedal as the first
+/// extra button. Layouts are shared with the MIDI output.
+const DEFAULT_MIDI_MAP: &str = r#"{
+  "ground": {
+    "note36": [0, 1], "note37": [2, 3], "note38": [4, 5], "note39": [6, 7],
+    "note40": [8, 9], "note41": [10, 11], "note42": [12, 13], "note43": [14, 15],
+    "note44": [16, 17], "note45": [18, 19], "note46": [20, 21], "note47": [22, 23],
+    "note48": [24, 25], "note49": [26, 27], "note50": [28, 29], "note51": [30, 31]
+  },
+  "air": {
+    "cc16": 0, "cc17": 1, "cc18": 2, "cc19": 3, "cc20": 4, "cc21": 5
+  },
+  "extra": {
+    "cc64": 0
+  }
+}"#;
+
+pub fn load_midi_map(name: &str) -> Result<MidiMap, String> {
+  MidiMap::load(
+    system::get_midi_layout_dir(),
+    name,
+    &[("default", DEFAULT_MIDI_MAP)],
+    MidiControl::from_name,
+  )
+}
+
+/// State owned by the midir callback thread.
+struct MidiReceiver {
+  state: SliderState,
+  midi_map: MidiMap,
+  values: HashMap<MidiControl, u8>,
+}
+
+impl MidiReceiver {
+  fn handle_message(&mut self, message: &[u8]) {
+    let (status, a, b) = match message {
+      [status, a, b, ..] => (status & 0xf0, *a, *b),
+      _ => return,
+    };
+
+    // Velocity and pressure are 0-127, scale to the 0-255 of slider pressure
+    let pressure = |x: u8| match x {
+      0 => 0,
+      x => x.min(127) * 2 + 1,
+    };
+
+    match (status, a) {
+      (0x80, note) => {
+        self.values.remove(&MidiControl::Note(note));
+      }
+      (0x90, note) => {
+        self.values.insert(MidiControl::Note(note), pressure(b));
+      }
+      // Polyphonic aftertouch updates the pressure of held notes
+      (0xa0, note) => {
+        if let Some(value) = self.values.get_mut(&MidiControl::Note(note)) {
+          *value = pressure(b).max(1);
+        }
+      }
+      // All notes off
+      (0xb0, 123) => self.values.retain(|control, _| match control {
+        MidiControl::Note(_) => false,
+        MidiControl::Cc(_) => true,
+      }),
+      // Controls are pressed from halfway, like a sustain pedal
+      (0xb0, cc) => {
+        self.values.insert(
+          MidiControl::Cc(cc),
+          match b >= 64 {
+            true => 255,
+            false => 0,
+          },
+        );
+      }
+      _ => return,
+    }
+
+    let mut input_handle = self.state.input.lock();
+    self.midi_map.apply_values(
+      self.values.iter().map(|(control, value)| (control, *value)),
+      &mut input_handle,
+    );
+  }
+}
+
+/// Reads a MIDI input port, such as a pad board, and uses it as a slider.
+pub struct MidiJob {
+  state: SliderState,
+  port: String,
+  layout: String,
+  errors: ErrorLog,
+}
+
+impl MidiJob {
+  pub fn new(state: &SliderState, port: &String, layout: &String, errors: &ErrorLog) -> Self {
+    Self {
+      state: state.clone(),
+      port: port.clone(),
+      layout: layout.clone(),
+      errors: errors.clone(),
+    }
+  }
+
+  fn connect(&self) -> Result<MidiInputConnection<MidiReceiver>, String> {
+    let midi_map = load_midi_map(&self.layout)
+      .map_err(|e| format!("MIDI layout {} could not be loaded: {}", self.layout, e))?;
+
+    let midi_in = MidiInput::new("slidershim").map_err(|e| format!("MIDI input error: {}", e))?;
+    let ports = midi_in.ports();
+    for port in ports.iter() {
+      info!(
+        "MIDI input found port {}",
+        midi_in.port_name(port).unwrap_or_default()
+      );
+    }
+
+    // An empty name picks the first port, otherwise match part of the name
+    let needle = self.port.to_lowercase();
+    let port = ports
+      .iter()
+      .find(|port| {
+        midi_in
+          .port_name(port)
+          .map(|name| name.to_lowercase().contains(&needle))
+          .unwrap_or(false)
+      })
+      .ok_or(format!("MIDI input port \"{}\" not found", self.port))?;
+    let port_name = midi_in.port_name(port).unwrap_or_default();
+
+    let connection = midi_in
+      .connect(
+        port,
+        "slidershim-in",
+        |_, message, receiver: &mut MidiReceiver| receiver.handle_message(message),
+        MidiReceiver {
+          state: self.state.clone(),
+          midi_map,
+          values: HashMap::new(),
+        },
+      )
+      .map_err(|e| format!("MIDI input port {} could not be opened: {}", port_name, e))?;
+    info!("MIDI input connected to {}", port_name);
+
+    Ok(connection)
+  }
+}
+
+#[async_trait]
+impl AsyncHaltableJob for MidiJob {
+  async fn run<F: Future<Output = ()> + Send>(self, stop_signal: F) {
+    // Messages are handled on the midir thread until the connection is closed
+    match self.connect() {
+      Ok(connection) => {
+        stop_signal.await;
+        connection.close();
+      }
+      Err(e) => self.errors.push(e),
+    }
+
+    let mut input_handle = self.state.input.lock();
+    input_handle.ground.fill(0);
+    input_handle.air.fill(0);
+    input_handle.extra.fill(0);
+  }
+}
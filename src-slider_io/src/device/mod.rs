@@ -1,5 +1,13 @@
 pub mod config;
 
 pub mod brokenithm;
+pub mod brokenithm_metrics;
+pub mod color;
 pub mod diva;
+pub mod gamepad;
 pub mod hid;
+pub mod hid_report;
+pub mod input_map;
+pub mod keyboard;
+pub mod midi;
+pub mod network;
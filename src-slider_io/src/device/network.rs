@@ -0,0 +1,341 @@
+use async_trait::async_trait;
+use futures::future;
+use log::{error, info};
+use std::{
+  future::Future,
+  net::{IpAddr, SocketAddr},
+};
+use tokio::{
+  io::{AsyncReadExt, AsyncWriteExt},
+  net::{TcpListener, TcpStream, UdpSocket},
+  pin, select,
+  sync::mpsc,
+  time::{interval, Duration, Instant},
+};
+
+use crate::{
+  shared::{
+    net,
+    remote::{RemoteFrame, RemoteKind, RemoteTransport, SeqTracker, MAX_FRAME_LEN},
+    utils::ErrorLog,
+    worker::AsyncHaltableJob,
+  },
+  state::SliderState,
+};
+
+/// Input is released if a peer sends nothing for this long.
+const PEER_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Lights are resent at least this often so that lost frames are recovered.
+const LIGHTS_KEEPALIVE: Duration = Duration::from_millis(1000);
+
+/// Receive side state for the remote slidershim currently sending input.
+struct Peer {
+  addr: SocketAddr,
+  tracker: SeqTracker,
+  last_input: Instant,
+  last_lights: Instant,
+  lights_seq: u32,
+}
+
+impl Peer {
+  fn new(addr: SocketAddr) -> Self {
+    info!("Network peer connected {}", addr);
+    Self {
+      addr,
+      tracker: SeqTracker::new(),
+      last_input: Instant::now(),
+      last_lights: Instant::now() - LIGHTS_KEEPALIVE,
+      lights_seq: 0,
+    }
+  }
+
+  fn handle_frame(&mut self, frame: &RemoteFrame, state: &SliderState) {
+    match frame.kind {
+      RemoteKind::Hello => {
+        self.tracker.restart();
+        self.last_input = Instant::now();
+      }
+      RemoteKind::Input => {
+        if self.tracker.accept(frame.seq) {
+          let mut input_handle = state.input.lock();
+          frame.apply_input(&mut input_handle);
+          self.last_input = Instant::now();
+        }
+      }
+      RemoteKind::Lights => {}
+    }
+  }
+
+  fn timed_out(&self) -> bool {
+    self.last_input.elapsed() > PEER_TIMEOUT
+  }
+
+  /// Takes a lights frame to send back if lights changed or are due for a
+  /// resend.
+  fn poll_lights(&mut self, state: &SliderState) -> Option<Vec<u8>> {
+    let mut lights_handle = state.lights.lock();
+    if !lights_handle.dirty && self.last_lights.elapsed() < LIGHTS_KEEPALIVE {
+      return None;
+    }
+    lights_handle.dirty = false;
+
+    let frame = RemoteFrame::lights(self.lights_seq, &lights_handle.output());
+    self.lights_seq = self.lights_seq.wrapping_add(1);
+    self.last_lights = Instant::now();
+
+    Some(frame.serialize())
+  }
+
+  fn disconnect(&self, state: &SliderState) {
+    info!(
+      "Network peer disconnected {} received:{} lost:{} stale:{}",
+      self.addr, self.tracker.received, self.tracker.lost, self.tracker.stale
+    );
+    let mut input_handle = state.input.lock();
+    input_handle.ground.fill(0);
+    input_handle.air.fill(0);
+    input_handle.extra.fill(0);
+  }
+}
+
+pub struct NetworkJob {
+  state: SliderState,
+  transport: RemoteTransport,
+  port: u16,
+  bind: Vec<IpAddr>,
+  errors: ErrorLog,
+}
+
+impl NetworkJob {
+  pub fn new(
+    state: &SliderState,
+    transport: &RemoteTransport,
+    port: &u16,
+    bind: &Vec<IpAddr>,
+    errors: &ErrorLog,
+  ) -> Self {
+    Self {
+      state: state.clone(),
+      transport: *transport,
+      port: *port,
+      bind: bind.clone(),
+      errors: errors.clone(),
+    }
+  }
+
+  fn bind_addrs(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+    self.bind.iter().map(|ip| SocketAddr::new(*ip, self.port))
+  }
+
+  /// Serves one peer at a time across every socket. Datagrams from other
+  /// addresses are ignored until the current peer times out, so that other
+  /// hosts on the network cannot take over the slider.
+  async fn run_udp(&self, sockets: Vec<UdpSocket>) {
+    let (tx, mut rx) = mpsc::channel(16);
+    let readers = future::join_all(sockets.iter().enumerate().map(|(i, socket)| {
+      let tx = tx.clone();
+      async move {
+        let mut buf = [0; MAX_FRAME_LEN];
+        loop {
+          match socket.recv_from(&mut buf).await {
+            Ok((len, addr)) => {
+              if tx.send((i, addr, buf[..len].to_vec())).await.is_err() {
+                break;
+              }
+            }
+            // Windows reports unreachable peers from earlier sends here
+            Err(e) => info!("Network input receive error: {}", e),
+          }
+        }
+      }
+    }));
+
+    let handler = async {
+      // The peer and the index of the socket it talks to
+      let mut peer: Option<(usize, Peer)> = None;
+      let mut timer = interval(Duration::from_millis(10));
+
+      loop {
+        select! {
+          Some((i, addr, data)) = rx.recv() => {
+            if let Ok(Some((frame, _))) = RemoteFrame::parse(&data) {
+              if peer.is_none() {
+                peer = Some((i, Peer::new(addr)));
+              }
+              if let Some((_, peer)) = peer.as_mut().filter(|(_, p)| p.addr == addr) {
+                peer.handle_frame(&frame, &self.state);
+              }
+            }
+          }
+          _ = timer.tick() => {
+            if peer.as_ref().map(|(_, p)| p.timed_out()).unwrap_or(false) {
+              if let Some((_, old_peer)) = peer.take() {
+                old_peer.disconnect(&self.state);
+              }
+            }
+            if let Some((i, peer)) = peer.as_mut() {
+              if let Some(data) = peer.poll_lights(&self.state) {
+                sockets[*i].send_to(&data, peer.addr).await.ok();
+              }
+            }
+          }
+        }
+      }
+    };
+
+    select! {
+      _ = readers => {}
+      _ = handler => {}
+    }
+  }
+
+  async fn handle_tcp(&self, mut stream: TcpStream, addr: SocketAddr) {
+    stream.set_nodelay(true).ok();
+    let mut peer = Peer::new(addr);
+    let mut pending: Vec<u8> = vec![];
+    let mut buf = [0; 1024];
+    let mut timer = interval(Duration::from_millis(10));
+
+    loop {
+      select! {
+        res = stream.read(&mut buf) => match res {
+          Ok(0) => break,
+          Ok(len) => {
+            pending.extend(&buf[..len]);
+            let mut used = 0;
+            loop {
+              match RemoteFrame::parse(&pending[used..]) {
+                Ok(Some((frame, len))) => {
+                  peer.handle_frame(&frame, &self.state);
+                  used += len;
+                }
+                Ok(None) => break,
+                Err(_) => {
+                  error!("Network input received invalid data from {}", addr);
+                  peer.disconnect(&self.state);
+                  return;
+                }
+              }
+            }
+            pending.drain(..used);
+          }
+          Err(e) => {
+            error!("Network input connection error: {}", e);
+            break;
+          }
+        },
+        _ = timer.tick() => {
+          if peer.timed_out() {
+            break;
+          }
+          if let Some(data) = peer.poll_lights(&self.state) {
+            if stream.write_all(&data).await.is_err() {
+              break;
+            }
+          }
+        }
+      }
+    }
+
+    peer.disconnect(&self.state);
+  }
+
+  /// Serves one connection at a time, later connections are closed until it
+  /// ends.
+  async fn run_tcp(&self, listeners: Vec<TcpListener>) {
+    let (tx, mut rx) = mpsc::channel(16);
+    let acceptors = future::join_all(listeners.iter().map(|listener| {
+      let tx = tx.clone();
+      async move {
+        loop {
+          match listener.accept().await {
+            Ok(conn) => {
+              if tx.send(conn).await.is_err() {
+                break;
+              }
+            }
+            Err(e) => error!("Network input accept error: {}", e),
+          }
+        }
+      }
+    }));
+
+    let handler = async {
+      while let Some((stream, addr)) = rx.recv().await {
+        let connection = self.handle_tcp(stream, addr);
+        pin!(connection);
+        loop {
+          select! {
+            _ = &mut connection => break,
+            Some((_, other)) = rx.recv() => {
+              info!("Network input rejected {}, already serving {}", other, addr);
+            }
+          }
+        }
+      }
+    };
+
+    select! {
+      _ = acceptors => {}
+      _ = handler => {}
+    }
+  }
+}
+
+#[async_trait]
+impl AsyncHaltableJob for NetworkJob {
+  async fn run<F: Future<Output = ()> + Send>(self, stop_signal: F) {
+    match self.transport {
+      RemoteTransport::Udp => {
+        let mut sockets = vec![];
+        for addr in self.bind_addrs() {
+          match net::bind_udp(&addr).and_then(UdpSocket::from_std) {
+            Ok(socket) => {
+              info!("Network input listening on udp {}", addr);
+              sockets.push(socket);
+            }
+            Err(e) => self
+              .errors
+              .push(net::describe_bind_error("Network input", &addr, &e)),
+          }
+        }
+        if sockets.is_empty() {
+          return;
+        }
+
+        select! {
+          _ = stop_signal => {}
+          _ = self.run_udp(sockets) => {}
+        }
+      }
+      RemoteTransport::Tcp => {
+        let mut listeners = vec![];
+        for addr in self.bind_addrs() {
+          match net::bind_tcp(&addr).and_then(TcpListener::from_std) {
+            Ok(listener) => {
+              info!("Network input listening on tcp {}", addr);
+              listeners.push(listener);
+            }
+            Err(e) => self
+              .errors
+              .push(net::describe_bind_error("Network input", &addr, &e)),
+          }
+        }
+        if listeners.is_empty() {
+          return;
+        }
+
+        select! {
+          _ = stop_signal => {}
+          _ = self.run_tcp(listeners) => {}
+        }
+      }
+    }
+
+    let mut input_handle = self.state.input.lock();
+    input_handle.ground.fill(0);
+    input_handle.air.fill(0);
+    input_handle.extra.fill(0);
+  }
+}
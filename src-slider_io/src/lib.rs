@@ -20,4 +20,8 @@ pub mod manager;
 
 pub use config::Config;
 pub use manager::Manager;
-pub use system::{get_brokenithm_qr_path, get_log_file_path, list_ips};
+pub use system::{
+  get_brokenithm_link, get_brokenithm_qr, get_brokenithm_www_dir, get_gamepad_layout_dir,
+  get_keyboard_layout_dir, get_lan_ip, get_lighting_effect_dir, get_lighting_recording_dir,
+  get_log_file_path, get_midi_layout_dir, list_ips, QrFormat,
+};
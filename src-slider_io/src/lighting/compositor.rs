@@ -0,0 +1,125 @@
+use async_trait::async_trait;
+use tokio::time::{interval, Duration, Interval};
+
+use crate::{
+  shared::worker::AsyncJob,
+  state::{SliderLights, SliderState},
+};
+
+use super::config::{BlendMode, LayerRegion, LightsLayer};
+
+fn blend_channel(blend: BlendMode, below: u8, above: u8) -> u8 {
+  let (b, a) = (below as u16, above as u16);
+  (match blend {
+    BlendMode::Normal | BlendMode::Fill => a,
+    BlendMode::Add => (b + a).min(255),
+    BlendMode::Multiply => b * a / 255,
+    BlendMode::Screen => 255 - (255 - b) * (255 - a) / 255,
+    BlendMode::Lighten => b.max(a),
+  }) as u8
+}
+
+/// Blends RGB pixels of `above` onto `below` in place.
+fn blend_pixels(below: &mut [u8], above: &[u8], blend: BlendMode, opacity: f32) {
+  for (b, a) in below.chunks_mut(3).zip(above.chunks(3)) {
+    if let BlendMode::Fill = blend {
+      if b.iter().any(|x| *x > 0) {
+        continue;
+      }
+    }
+
+    for i in 0..3 {
+      let target = blend_channel(blend, b[i], a[i]) as f32;
+      b[i] = (b[i] as f32 + (target - b[i] as f32) * opacity).round() as u8;
+    }
+  }
+}
+
+/// Renders the main lighting mode and every layer into their own
+/// `SliderLights` and blends them bottom to top into the slider lights.
+pub struct CompositorJob {
+  state: SliderState,
+  base: SliderState,
+  layers: Vec<(LightsLayer, SliderState)>,
+  timer: Interval,
+}
+
+impl CompositorJob {
+  /// `base` and each layer state should come from `SliderState::fork_lights`
+  /// and have their own lighting worker.
+  pub fn new(
+    state: &SliderState,
+    base: &SliderState,
+    layers: &Vec<(LightsLayer, SliderState)>,
+  ) -> Self {
+    Self {
+      state: state.clone(),
+      base: base.clone(),
+      layers: layers.clone(),
+      timer: interval(Duration::from_micros(33333)),
+    }
+  }
+}
+
+#[async_trait]
+impl AsyncJob for CompositorJob {
+  async fn setup(&mut self) -> bool {
+    true
+  }
+
+  async fn tick(&mut self) -> bool {
+    self.timer.tick().await;
+
+    let mut composite = SliderLights::new();
+    let mut dirty = false;
+    {
+      let mut base_handle = self.base.lights.lock();
+      composite.ground = base_handle.ground;
+      composite.air_left = base_handle.air_left;
+      composite.air_right = base_handle.air_right;
+      composite.last_frame = base_handle.last_frame;
+      dirty |= base_handle.dirty;
+      base_handle.dirty = false;
+    }
+
+    for (layer, layer_state) in self.layers.iter() {
+      let mut layer_handle = layer_state.lights.lock();
+      if let LayerRegion::All | LayerRegion::Ground = layer.region {
+        blend_pixels(
+          &mut composite.ground,
+          &layer_handle.ground,
+          layer.blend,
+          layer.opacity,
+        );
+      }
+      if let LayerRegion::All | LayerRegion::Air = layer.region {
+        blend_pixels(
+          &mut composite.air_left,
+          &layer_handle.air_left,
+          layer.blend,
+          layer.opacity,
+        );
+        blend_pixels(
+          &mut composite.air_right,
+          &layer_handle.air_right,
+          layer.blend,
+          layer.opacity,
+        );
+      }
+      composite.last_frame = composite.last_frame.max(layer_handle.last_frame);
+      dirty |= layer_handle.dirty;
+      layer_handle.dirty = false;
+    }
+
+    if dirty {
+      let mut lights_handle = self.state.lights.lock();
+      lights_handle.ground = composite.ground;
+      lights_handle.air_left = composite.air_left;
+      lights_handle.air_right = composite.air_right;
+      lights_handle.last_frame = composite.last_frame;
+      lights_handle.dirty = true;
+    }
+
+    true
+  }
+}
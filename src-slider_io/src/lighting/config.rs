@@ -1,13 +1,10 @@
 use serde_json::Value;
+use std::{net::IpAddr, time::Duration};
 
-#[derive(Debug, Clone, Copy)]
-pub enum ReactiveLayout {
-  Even { splits: usize },
-  Six,
-  Voltex,
-  Hori,
-  Rainbow,
-}
+use crate::shared::{
+  dmx::{DmxProtocol, MAX_START_CHANNEL},
+  net::parse_bind_addrs,
+};
 
 #[derive(Debug, Clone)]
 pub struct ColorScheme {
@@ -64,14 +61,66 @@ impl ColorScheme {
   }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum FadeCurve {
+  Linear,
+  Smooth,
+  Exponential,
+}
+
+impl FadeCurve {
+  pub fn from_str(s: &str) -> Option<Self> {
+    Some(match s {
+      "linear" => FadeCurve::Linear,
+      "smooth" => FadeCurve::Smooth,
+      "exponential" => FadeCurve::Exponential,
+      _ => return None,
+    })
+  }
+
+  /// Maps the remaining fade from 1 to 0 onto brightness.
+  pub fn apply(&self, t: f32) -> f32 {
+    match self {
+      FadeCurve::Linear => t,
+      FadeCurve::Smooth => t * t * (3.0 - 2.0 * t),
+      FadeCurve::Exponential => (2f32.powf(10.0 * t) - 1.0) / 1023.0,
+    }
+  }
+}
+
+/// Time based effects for reactive lighting, applied to every bank of the
+/// effect definition. Zero durations turn an effect off.
+#[derive(Debug, Clone)]
+pub struct ReactiveEffects {
+  pub afterglow: Duration,
+  pub ripple: Duration,
+  pub curve: FadeCurve,
+}
+
+impl ReactiveEffects {
+  pub fn from_serde_value(v: &Value) -> Option<Self> {
+    Some(Self {
+      afterglow: Duration::from_millis(
+        u16::try_from(v["ledAfterglow"].as_i64().unwrap_or(0)).ok()? as u64,
+      ),
+      ripple: Duration::from_millis(
+        u16::try_from(v["ledRipple"].as_i64().unwrap_or(0)).ok()? as u64
+      ),
+      curve: FadeCurve::from_str(v["ledFadeCurve"].as_str().unwrap_or("linear"))?,
+    })
+  }
+}
+
 #[derive(Debug, Clone)]
 pub enum LightsMode {
   None,
   Reactive {
     faster: bool,
-    layout: ReactiveLayout,
+    /// Name of the lighting effect definition.
+    effect: String,
     sensitivity: u8,
     color: ColorScheme,
+    effects: ReactiveEffects,
   },
   Attract {
     faster: bool,
@@ -83,58 +132,49 @@ pub enum LightsMode {
   UmgrWebsocket {
     faster: bool,
     port: u16,
+    bind: Vec<IpAddr>,
   },
   Serial {
     faster: bool,
     port: String,
   },
+  Script {
+    faster: bool,
+    /// Name of the script file.
+    script: String,
+    sensitivity: u8,
+  },
+  Dmx {
+    protocol: DmxProtocol,
+    universe: u16,
+    /// First channel of the lights, counting from 1.
+    start_channel: usize,
+    bind: Vec<IpAddr>,
+  },
+  Playback {
+    /// Name of the file in the lighting recording directory.
+    file: String,
+    looped: bool,
+    /// 1 plays at the recorded speed.
+    speed: f32,
+  },
 }
 
 impl LightsMode {
   pub fn from_serde_value(v: &Value) -> Option<Self> {
     Some(match v["ledMode"].as_str()? {
       "none" => LightsMode::None,
-      "reactive-16" => LightsMode::Reactive {
-        faster: v["ledFaster"].as_bool()?,
-        layout: ReactiveLayout::Even { splits: 16 },
-        sensitivity: u8::try_from(v["ledSensitivity"].as_i64()?).ok()?,
-        color: ColorScheme::from_serde_value_or_default(v),
-      },
-      "reactive-8" => LightsMode::Reactive {
-        faster: v["ledFaster"].as_bool()?,
-        layout: ReactiveLayout::Even { splits: 8 },
-        sensitivity: u8::try_from(v["ledSensitivity"].as_i64()?).ok()?,
-        color: ColorScheme::from_serde_value_or_default(v),
-      },
-      "reactive-6" => LightsMode::Reactive {
+      x if x.starts_with("reactive-") => LightsMode::Reactive {
         faster: v["ledFaster"].as_bool()?,
-        layout: ReactiveLayout::Six,
+        effect: match &x[9..] {
+          "16" | "8" | "6" | "4" => format!("{}-zone", &x[9..]),
+          "rainbow" | "voltex" | "hori" => x[9..].to_string(),
+          "custom" => v["ledEffect"].as_str()?.to_string(),
+          _ => return None,
+        },
         sensitivity: u8::try_from(v["ledSensitivity"].as_i64()?).ok()?,
         color: ColorScheme::from_serde_value_or_default(v),
-      },
-      "reactive-4" => LightsMode::Reactive {
-        faster: v["ledFaster"].as_bool()?,
-        layout: ReactiveLayout::Even { splits: 4 },
-        sensitivity: u8::try_from(v["ledSensitivity"].as_i64()?).ok()?,
-        color: ColorScheme::from_serde_value_or_default(v),
-      },
-      "reactive-rainbow" => LightsMode::Reactive {
-        faster: v["ledFaster"].as_bool()?,
-        layout: ReactiveLayout::Rainbow,
-        sensitivity: u8::try_from(v["ledSensitivity"].as_i64()?).ok()?,
-        color: ColorScheme::default(),
-      },
-      "reactive-voltex" => LightsMode::Reactive {
-        faster: v["ledFaster"].as_bool()?,
-        layout: ReactiveLayout::Voltex,
-        sensitivity: u8::try_from(v["ledSensitivity"].as_i64()?).ok()?,
-        color: ColorScheme::default(),
-      },
-      "reactive-hori" => LightsMode::Reactive {
-        faster: v["ledFaster"].as_bool()?,
-        layout: ReactiveLayout::Hori,
-        sensitivity: u8::try_from(v["ledSensitivity"].as_i64()?).ok()?,
-        color: ColorScheme::default(),
+        effects: ReactiveEffects::from_serde_value(v)?,
       },
       "attract" => LightsMode::Attract {
         faster: v["ledFaster"].as_bool()?,
@@ -146,12 +186,156 @@ impl LightsMode {
       "umgr-websocket" => LightsMode::UmgrWebsocket {
         faster: v["ledFaster"].as_bool()?,
         port: u16::try_from(v["ledUmgrWebsocketPort"].as_i64()?).ok()?,
+        bind: parse_bind_addrs(v["ledUmgrWebsocketBindAddrs"].as_str().unwrap_or("0.0.0.0")),
       },
       "serial" => LightsMode::Serial {
         faster: v["ledFaster"].as_bool()?,
         port: v["ledSerialPort"].as_str()?.to_string(),
       },
+      "script" => LightsMode::Script {
+        faster: v["ledFaster"].as_bool()?,
+        script: v["ledScript"].as_str()?.to_string(),
+        sensitivity: u8::try_from(v["ledSensitivity"].as_i64()?).ok()?,
+      },
+      "playback" => LightsMode::Playback {
+        file: v["ledPlaybackFile"].as_str()?.to_string(),
+        looped: v["ledPlaybackLoop"].as_bool().unwrap_or(true),
+        speed: match v["ledPlaybackSpeed"].as_u64().unwrap_or(100) {
+          x @ 10..=1000 => x as f32 / 100.0,
+          _ => return None,
+        },
+      },
+      x if x.starts_with("dmx-") => {
+        let protocol = DmxProtocol::from_str(&x[4..])?;
+        LightsMode::Dmx {
+          protocol,
+          universe: protocol.parse_universe(&v["ledDmxUniverse"])?,
+          start_channel: match v["ledDmxStartChannel"].as_u64()? as usize {
+            x @ 1..=MAX_START_CHANNEL => x,
+            _ => return None,
+          },
+          bind: parse_bind_addrs(v["ledDmxBindAddrs"].as_str().unwrap_or("0.0.0.0")),
+        }
+      }
       _ => return None,
     })
   }
 }
+
+/// Switches to the attract rainbow when the slider has not been touched and
+/// no lights were received from the game for a while.
+#[derive(Debug, Clone)]
+pub struct IdleFallback {
+  pub timeout: Duration,
+  /// Touch pressure that counts as a touch.
+  pub sensitivity: u8,
+  pub attract: LightsMode,
+}
+
+impl IdleFallback {
+  /// Returns `None` if the fallback is disabled with a timeout of 0.
+  pub fn from_serde_value(v: &Value) -> Option<Self> {
+    let timeout = v["ledIdleTimeout"].as_u64().unwrap_or(0);
+    if timeout == 0 {
+      return None;
+    }
+
+    Some(Self {
+      timeout: Duration::from_secs(timeout),
+      sensitivity: u8::try_from(v["ledSensitivity"].as_i64()?).ok()?,
+      attract: LightsMode::Attract {
+        faster: v["ledFaster"].as_bool()?,
+      },
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BlendMode {
+  Normal,
+  Add,
+  Multiply,
+  Screen,
+  Lighten,
+  /// Only paints pixels that are still black below this layer.
+  Fill,
+}
+
+impl BlendMode {
+  pub fn from_str(s: &str) -> Option<Self> {
+    Some(match s {
+      "normal" => BlendMode::Normal,
+      "add" => BlendMode::Add,
+      "multiply" => BlendMode::Multiply,
+      "screen" => BlendMode::Screen,
+      "lighten" => BlendMode::Lighten,
+      "fill" => BlendMode::Fill,
+      _ => return None,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LayerRegion {
+  All,
+  Ground,
+  Air,
+}
+
+impl LayerRegion {
+  pub fn from_str(s: &str) -> Option<Self> {
+    Some(match s {
+      "all" => LayerRegion::All,
+      "ground" => LayerRegion::Ground,
+      "air" => LayerRegion::Air,
+      _ => return None,
+    })
+  }
+}
+
+/// A lighting source drawn over the main `ledMode` and the layers before it.
+#[derive(Debug, Clone)]
+pub struct LightsLayer {
+  pub mode: LightsMode,
+  /// 0 to 1
+  pub opacity: f32,
+  pub blend: BlendMode,
+  pub region: LayerRegion,
+}
+
+impl LightsLayer {
+  /// Parses every entry of `ledLayers`, bottom to top. Like `extraOutputs`,
+  /// entries only need the keys that differ from the main LED settings, e.g.
+  /// `{"ledMode": "reactive-16", "ledLayerRegion": "air", "ledLayerBlend":
+  /// "fill"}`.
+  pub fn list_from_serde_value(v: &Value) -> Option<Vec<Self>> {
+    let mut layers = vec![];
+
+    if let Value::Array(led_layers) = &v["ledLayers"] {
+      for led_layer in led_layers {
+        let mut merged = v.clone();
+        for (key, value) in led_layer.as_object()? {
+          if !value.is_null() {
+            merged[key] = value.clone();
+          }
+        }
+
+        let mode = LightsMode::from_serde_value(&merged)?;
+        if let LightsMode::None = mode {
+          continue;
+        }
+        layers.push(Self {
+          mode,
+          opacity: (u8::try_from(led_layer["ledLayerOpacity"].as_i64().unwrap_or(100))
+            .ok()?
+            .min(100) as f32)
+            / 100.0,
+          blend: BlendMode::from_str(led_layer["ledLayerBlend"].as_str().unwrap_or("normal"))?,
+          region: LayerRegion::from_str(led_layer["ledLayerRegion"].as_str().unwrap_or("all"))?,
+        });
+      }
+    }
+
+    Some(layers)
+  }
+}
@@ -0,0 +1,244 @@
+use serde_json::Value;
+use std::{fs, time::Duration};
+
+use crate::system;
+
+/// Colors in an effect definition, either `#rrggbb`, one of the user's LED
+/// colors, or a rainbow that scrolls across the slider.
+#[derive(Debug, Clone, Copy)]
+pub enum EffectColor {
+  Rgb([u8; 3]),
+  Active,
+  Inactive,
+  AirActive,
+  AirInactive,
+  Rainbow,
+  RainbowPale,
+  /// Leaves the LED as painted by the background or earlier entries.
+  None,
+}
+
+impl EffectColor {
+  fn from_serde_value(v: &Value) -> Option<Self> {
+    let s = v.as_str()?;
+    Some(match s {
+      "active" => EffectColor::Active,
+      "inactive" => EffectColor::Inactive,
+      "airActive" => EffectColor::AirActive,
+      "airInactive" => EffectColor::AirInactive,
+      "rainbow" => EffectColor::Rainbow,
+      "rainbowPale" => EffectColor::RainbowPale,
+      "none" => EffectColor::None,
+      x if x.len() == 7 && x.starts_with('#') => EffectColor::Rgb([
+        u8::from_str_radix(&x[1..3], 16).ok()?,
+        u8::from_str_radix(&x[3..5], 16).ok()?,
+        u8::from_str_radix(&x[5..7], 16).ok()?,
+      ]),
+      _ => return None,
+    })
+  }
+
+  fn from_serde_value_or(v: &Value, default: Self) -> Option<Self> {
+    match v {
+      Value::Null => Some(default),
+      v => Self::from_serde_value(v),
+    }
+  }
+}
+
+/// Parses an index, a `"first-last"` range, a `"first-last/step"` range or an
+/// array of those. Every index must be below `len`.
+fn parse_indices(v: &Value, len: usize) -> Option<Vec<usize>> {
+  let idxs: Vec<usize> = match v {
+    Value::Null => vec![],
+    Value::Number(x) => vec![x.as_u64()? as usize],
+    Value::String(x) => {
+      let (range, step) = match x.split_once('/') {
+        Some((range, step)) => (range, step.trim().parse().ok()?),
+        None => (x.as_str(), 1),
+      };
+      let (first, last) = match range.split_once('-') {
+        Some((first, last)) => (first.trim().parse().ok()?, last.trim().parse().ok()?),
+        None => (range.trim().parse().ok()?, range.trim().parse().ok()?),
+      };
+      if step == 0 || first > last {
+        return None;
+      }
+      (first..=last).step_by(step).collect()
+    }
+    Value::Array(xs) => {
+      let mut idxs = vec![];
+      for x in xs {
+        idxs.extend(parse_indices(x, len)?);
+      }
+      idxs
+    }
+    _ => return None,
+  };
+
+  match idxs.iter().all(|idx| *idx < len) {
+    true => Some(idxs),
+    false => None,
+  }
+}
+
+/// LEDs painted with a color regardless of input.
+#[derive(Debug, Clone)]
+pub struct FixedLeds {
+  pub leds: Vec<usize>,
+  pub color: EffectColor,
+}
+
+impl FixedLeds {
+  fn from_serde_value(v: &Value) -> Option<Self> {
+    Some(Self {
+      leds: parse_indices(&v["leds"], 31)?,
+      color: EffectColor::from_serde_value(&v["color"])?,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AirSide {
+  Both,
+  Left,
+  Right,
+}
+
+/// LEDs lit when any of the triggering inputs is touched.
+#[derive(Debug, Clone)]
+pub struct EffectBank {
+  /// Indices into the flat input, ground pads then air strings then extra
+  /// buttons.
+  pub triggers: Vec<usize>,
+  pub leds: Vec<usize>,
+  pub side: AirSide,
+  pub active: EffectColor,
+  pub inactive: EffectColor,
+}
+
+impl EffectBank {
+  fn from_serde_value(v: &Value, leds_len: usize, air: bool) -> Option<Self> {
+    let mut triggers = vec![];
+    for (section, offset, len) in [("ground", 0, 32), ("air", 32, 6), ("extra", 38, 3)] {
+      triggers.extend(parse_indices(&v[section], len)?.iter().map(|x| x + offset));
+    }
+
+    let (active, inactive) = match air {
+      false => (EffectColor::Active, EffectColor::Inactive),
+      true => (EffectColor::AirActive, EffectColor::AirInactive),
+    };
+
+    Some(Self {
+      triggers,
+      leds: parse_indices(&v["leds"], leds_len)?,
+      side: match v["side"].as_str().unwrap_or("both") {
+        "both" => AirSide::Both,
+        "left" => AirSide::Left,
+        "right" => AirSide::Right,
+        _ => return None,
+      },
+      active: EffectColor::from_serde_value_or(&v["active"], active)?,
+      inactive: EffectColor::from_serde_value_or(&v["inactive"], inactive)?,
+    })
+  }
+
+  pub fn is_lit(&self, flat_input: &Vec<bool>) -> bool {
+    self.triggers.iter().any(|idx| flat_input[*idx])
+  }
+}
+
+/// A reactive lighting effect. Definition files look like
+///
+/// ```json
+/// {
+///   "background": "#000000",
+///   "fixed": [{"leds": "1-29/2", "color": "active"}],
+///   "banks": [{"ground": "0-3", "leds": "0-2/2", "active": "#ff0000"}],
+///   "air": [{"air": "0-1", "leds": 0, "side": "left"}],
+///   "rainbow": {"period": 4000, "spread": 32}
+/// }
+/// ```
+///
+/// Ground LEDs are numbered 0-30 from the left, even ones under the pads and
+/// odd ones on the dividers. Air LEDs are numbered 0-2 from the bottom. The
+/// background is painted first, then fixed LEDs, then banks in order.
+#[derive(Debug, Clone)]
+pub struct EffectDefinition {
+  pub background: Option<EffectColor>,
+  pub fixed: Vec<FixedLeds>,
+  pub banks: Vec<EffectBank>,
+  pub air: Vec<EffectBank>,
+  /// Time for the rainbow to scroll by one full cycle.
+  pub rainbow_period: Duration,
+  /// Number of ground LEDs covered by one full rainbow cycle.
+  pub rainbow_spread: f64,
+}
+
+const BUILTIN_EFFECTS: &[(&str, &str)] = &[
+  ("16-zone", include_str!("./lighting-effects/16-zone.json")),
+  ("8-zone", include_str!("./lighting-effects/8-zone.json")),
+  ("6-zone", include_str!("./lighting-effects/6-zone.json")),
+  ("4-zone", include_str!("./lighting-effects/4-zone.json")),
+  ("rainbow", include_str!("./lighting-effects/rainbow.json")),
+  ("voltex", include_str!("./lighting-effects/voltex.json")),
+  ("hori", include_str!("./lighting-effects/hori.json")),
+];
+
+impl EffectDefinition {
+  pub fn from_serde_value(v: &Value) -> Result<Self, String> {
+    let list = |section: &str| -> Result<Vec<Value>, String> {
+      match &v[section] {
+        Value::Null => Ok(vec![]),
+        Value::Array(xs) => Ok(xs.clone()),
+        _ => Err(format!("\"{}\" should be an array", section)),
+      }
+    };
+
+    let mut fixed = vec![];
+    for (i, x) in list("fixed")?.iter().enumerate() {
+      fixed.push(FixedLeds::from_serde_value(x).ok_or(format!("bad fixed entry {}", i + 1))?);
+    }
+
+    let mut banks = vec![];
+    for (i, x) in list("banks")?.iter().enumerate() {
+      banks.push(EffectBank::from_serde_value(x, 31, false).ok_or(format!("bad bank {}", i + 1))?);
+    }
+
+    let mut air = vec![];
+    for (i, x) in list("air")?.iter().enumerate() {
+      air.push(EffectBank::from_serde_value(x, 3, true).ok_or(format!("bad air entry {}", i + 1))?);
+    }
+
+    Ok(Self {
+      background: match &v["background"] {
+        Value::Null => None,
+        x => Some(EffectColor::from_serde_value(x).ok_or("bad background".to_string())?),
+      },
+      fixed,
+      banks,
+      air,
+      rainbow_period: Duration::from_millis(v["rainbow"]["period"].as_u64().unwrap_or(4000).max(1)),
+      rainbow_spread: v["rainbow"]["spread"].as_f64().unwrap_or(32.0),
+    })
+  }
+
+  /// Loads `<name>.json` from the lighting effect directory. Built-in effects
+  /// are used if there is no such file.
+  pub fn load(name: &str) -> Result<Self, String> {
+    let dir = system::get_lighting_effect_dir().ok_or("config directory not found".to_string())?;
+    let path = system::join_config_path(&dir, &format!("{}.json", name))
+      .ok_or(format!("\"{}\" is not an effect name", name))?;
+
+    let data = match fs::read_to_string(path.as_path()) {
+      Ok(data) => data,
+      Err(e) => match BUILTIN_EFFECTS.iter().find(|(x, _)| *x == name) {
+        Some((_, data)) => data.to_string(),
+        None => return Err(format!("{}: {}", path.display(), e)),
+      },
+    };
+    let v: Value = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+
+    Self::from_serde_value(&v)
+  }
+}
@@ -0,0 +1,135 @@
+use async_trait::async_trait;
+use futures::future;
+use log::info;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+  io,
+  net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket as StdUdpSocket},
+  time::Instant,
+};
+use tokio::{
+  net::UdpSocket,
+  time::{timeout, Duration},
+};
+
+use crate::{
+  shared::{
+    dmx::{channels_to_lights, DmxProtocol},
+    net::describe_bind_error,
+    utils::ErrorLog,
+    worker::AsyncJob,
+  },
+  state::SliderState,
+};
+
+/// How long to wait for a packet before checking whether the job should stop.
+const RECV_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Shares the port with other programs listening for the same protocol, as
+/// lighting consoles and visualisers often run on the same computer. IPv6
+/// sockets are v6-only like `net::bind_udp`.
+fn bind_udp(addr: &SocketAddr) -> io::Result<StdUdpSocket> {
+  let socket = Socket::new(Domain::for_address(*addr), Type::DGRAM, Some(Protocol::UDP))?;
+  if addr.is_ipv6() {
+    socket.set_only_v6(true)?;
+  }
+  socket.set_reuse_address(true)?;
+  socket.bind(&(*addr).into())?;
+  socket.set_nonblocking(true)?;
+
+  Ok(socket.into())
+}
+
+/// Drives the slider lights from a DMX universe received over sACN or
+/// Art-Net.
+pub struct DmxLightsJob {
+  state: SliderState,
+  protocol: DmxProtocol,
+  universe: u16,
+  start_channel: usize,
+  bind: Vec<IpAddr>,
+  /// One socket per listen address, each with its own receive buffer.
+  sockets: Vec<(UdpSocket, [u8; 1024])>,
+  errors: ErrorLog,
+}
+
+impl DmxLightsJob {
+  pub fn new(
+    state: &SliderState,
+    protocol: &DmxProtocol,
+    universe: &u16,
+    start_channel: &usize,
+    bind: &Vec<IpAddr>,
+    errors: &ErrorLog,
+  ) -> Self {
+    Self {
+      state: state.clone(),
+      protocol: *protocol,
+      universe: *universe,
+      start_channel: *start_channel,
+      bind: bind.clone(),
+      sockets: vec![],
+      errors: errors.clone(),
+    }
+  }
+}
+
+#[async_trait]
+impl AsyncJob for DmxLightsJob {
+  async fn setup(&mut self) -> bool {
+    let port = self.protocol.port();
+    for addr in self.bind.iter().map(|ip| SocketAddr::new(*ip, port)) {
+      let socket = match bind_udp(&addr).and_then(UdpSocket::from_std) {
+        Ok(socket) => socket,
+        Err(e) => {
+          self
+            .errors
+            .push(describe_bind_error("DMX lights", &addr, &e));
+          continue;
+        }
+      };
+
+      // sACN senders usually multicast, unicast packets arrive either way.
+      // Multicast is only joined over IPv4, on the interface of the address.
+      if let (DmxProtocol::Sacn, IpAddr::V4(interface)) = (self.protocol, addr.ip()) {
+        let group = Ipv4Addr::new(239, 255, (self.universe >> 8) as u8, self.universe as u8);
+        if let Err(e) = socket.join_multicast_v4(group, interface) {
+          self.errors.push(format!(
+            "DMX lights cannot join multicast group {} on {}: {}",
+            group, interface, e
+          ));
+        }
+      }
+
+      info!(
+        "DMX lights listening for {:?} universe {} on {}",
+        self.protocol, self.universe, addr
+      );
+      self.sockets.push((socket, [0; 1024]));
+    }
+
+    !self.sockets.is_empty()
+  }
+
+  async fn tick(&mut self) -> bool {
+    let recvs = self
+      .sockets
+      .iter_mut()
+      .map(|(socket, buf)| Box::pin(async move { (socket.recv(buf).await, buf) }));
+    let (len, buf) = match timeout(RECV_TIMEOUT, future::select_all(recvs)).await {
+      Ok(((Ok(len), buf), _, _)) => (len, buf),
+      _ => return true,
+    };
+
+    if let Some((universe, channels)) = self.protocol.parse(&buf[..len]) {
+      if universe == self.universe {
+        let mut lights_handle = self.state.lights.lock();
+        channels_to_lights(channels, self.start_channel, &mut lights_handle);
+        lights_handle.last_frame = Some(Instant::now());
+        lights_handle.dirty = true;
+      }
+    }
+
+    true
+  }
+}
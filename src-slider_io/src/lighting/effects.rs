@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use super::config::ReactiveEffects;
+
+/// How many pixels a ripple travels to each side before it fades out.
+const RIPPLE_REACH: f32 = 8.0;
+
+struct Ripple {
+  center: f32,
+  started: Duration,
+}
+
+/// Per pixel brightness for reactive layouts. Pixels light up instantly on
+/// touch and fade out over the afterglow time after release, presses also
+/// send ripples to neighbouring pixels. Everything is timed with the elapsed
+/// time passed to `update`, so effects look the same at any LED update rate.
+pub struct ReactiveGlow {
+  effects: ReactiveEffects,
+  last: Duration,
+  ground: [f32; 31],
+  air: [f32; 3],
+  last_ground_lit: [bool; 31],
+  ripples: Vec<Ripple>,
+}
+
+impl ReactiveGlow {
+  pub fn new(effects: &ReactiveEffects) -> Self {
+    Self {
+      effects: effects.clone(),
+      last: Duration::ZERO,
+      ground: [0.0; 31],
+      air: [0.0; 3],
+      last_ground_lit: [false; 31],
+      ripples: vec![],
+    }
+  }
+
+  fn decay(level: f32, lit: bool, dt: Duration, afterglow: Duration) -> f32 {
+    match (lit, afterglow.is_zero()) {
+      (true, _) => 1.0,
+      (false, true) => 0.0,
+      (false, false) => (level - dt.div_duration_f32(afterglow)).max(0.0),
+    }
+  }
+
+  /// Advances effects to `now`, the time since lighting started.
+  pub fn update(&mut self, ground_lit: &[bool; 31], air_lit: &[bool; 3], now: Duration) {
+    let dt = now.saturating_sub(self.last);
+    self.last = now;
+
+    let afterglow = self.effects.afterglow;
+    for (level, lit) in self.ground.iter_mut().zip(ground_lit.iter()) {
+      *level = Self::decay(*level, *lit, dt, afterglow);
+    }
+    for (level, lit) in self.air.iter_mut().zip(air_lit.iter()) {
+      *level = Self::decay(*level, *lit, dt, afterglow);
+    }
+
+    if !self.effects.ripple.is_zero() {
+      // A ripple starts from the middle of every newly lit run of pixels
+      let mut idx = 0;
+      while idx < 31 {
+        if ground_lit[idx] && !self.last_ground_lit[idx] {
+          let start = idx;
+          while idx < 31 && ground_lit[idx] && !self.last_ground_lit[idx] {
+            idx += 1;
+          }
+          self.ripples.push(Ripple {
+            center: (start + idx - 1) as f32 / 2.0,
+            started: now,
+          });
+        }
+        idx += 1;
+      }
+
+      let ripple = self.effects.ripple;
+      self
+        .ripples
+        .retain(|r| now.saturating_sub(r.started) < ripple);
+    }
+    self.last_ground_lit = *ground_lit;
+  }
+
+  fn ripple_level(&self, idx: usize) -> f32 {
+    self
+      .ripples
+      .iter()
+      .map(|r| {
+        let t = self
+          .last
+          .saturating_sub(r.started)
+          .div_duration_f32(self.effects.ripple);
+        let radius = t * RIPPLE_REACH;
+        let distance = ((idx as f32 - r.center).abs() - radius).abs();
+        (1.0 - distance).max(0.0) * (1.0 - t)
+      })
+      .fold(0.0, f32::max)
+  }
+
+  fn mix(&self, level: f32, active: &[u8; 3], inactive: &[u8; 3]) -> [u8; 3] {
+    let level = self.effects.curve.apply(level.clamp(0.0, 1.0));
+    let mut color = [0; 3];
+    for i in 0..3 {
+      color[i] =
+        (inactive[i] as f32 + (active[i] as f32 - inactive[i] as f32) * level).round() as u8;
+    }
+    color
+  }
+
+  pub fn ground(&self, idx: usize, active: &[u8; 3], inactive: &[u8; 3]) -> [u8; 3] {
+    let level = self.ground[idx].max(self.ripple_level(idx));
+    self.mix(level, active, inactive)
+  }
+
+  pub fn air(&self, idx: usize, active: &[u8; 3], inactive: &[u8; 3]) -> [u8; 3] {
+    self.mix(self.air[idx], active, inactive)
+  }
+}
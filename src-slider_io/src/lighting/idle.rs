@@ -0,0 +1,102 @@
+use async_trait::async_trait;
+use log::info;
+use std::time::Instant;
+use tokio::time::{interval, Duration, Interval};
+
+use crate::{
+  shared::worker::AsyncJob,
+  state::{SliderLights, SliderState},
+};
+
+use super::config::IdleFallback;
+
+fn copy_lights(from: &SliderLights, to: &mut SliderLights) {
+  to.ground = from.ground;
+  to.air_left = from.air_left;
+  to.air_right = from.air_right;
+  to.last_frame = from.last_frame;
+  to.dirty = true;
+}
+
+/// Passes the main lighting through to the slider lights, and switches to
+/// the attract lights once the slider has not been touched and no lights
+/// were received from the game for the idle timeout. The first touch or game
+/// frame switches back straight away.
+pub struct IdleJob {
+  state: SliderState,
+  active: SliderState,
+  attract: SliderState,
+  fallback: IdleFallback,
+  last_activity: Instant,
+  last_frame: Option<Instant>,
+  idle: bool,
+  timer: Interval,
+}
+
+impl IdleJob {
+  /// `active` and `attract` should come from `SliderState::fork_lights` and
+  /// have their own lighting worker.
+  pub fn new(
+    state: &SliderState,
+    active: &SliderState,
+    attract: &SliderState,
+    fallback: &IdleFallback,
+  ) -> Self {
+    Self {
+      state: state.clone(),
+      active: active.clone(),
+      attract: attract.clone(),
+      fallback: fallback.clone(),
+      last_activity: Instant::now(),
+      last_frame: None,
+      idle: false,
+      timer: interval(Duration::from_micros(33333)),
+    }
+  }
+}
+
+#[async_trait]
+impl AsyncJob for IdleJob {
+  async fn setup(&mut self) -> bool {
+    true
+  }
+
+  async fn tick(&mut self) -> bool {
+    self.timer.tick().await;
+
+    let touched = {
+      let input_handle = self.state.input.lock();
+      input_handle
+        .to_flat(&self.fallback.sensitivity)
+        .iter()
+        .any(|x| *x)
+    };
+    let last_frame = self.active.lights.lock().last_frame;
+    if touched || last_frame != self.last_frame {
+      self.last_activity = Instant::now();
+      self.last_frame = last_frame;
+    }
+
+    let idle = self.last_activity.elapsed() >= self.fallback.timeout;
+    let switched = idle != self.idle;
+    if switched {
+      info!("Lights {}", if idle { "idle" } else { "resumed" });
+    }
+    self.idle = idle;
+
+    // Lights are copied over on every switch even if they did not change, so
+    // that the other source is replaced straight away
+    let source = match idle {
+      false => &self.active,
+      true => &self.attract,
+    };
+    let mut source_handle = source.lights.lock();
+    if source_handle.dirty || switched {
+      let mut lights_handle = self.state.lights.lock();
+      copy_lights(&source_handle, &mut lights_handle);
+      source_handle.dirty = false;
+    }
+
+    true
+  }
+}
@@ -9,11 +9,19 @@ use std::{
 use tokio::time::{interval, Interval};
 
 use crate::{
-  shared::{hori::HoriState, utils::Buffer, voltex::VoltexState, worker::AsyncJob},
-  state::{SliderLights, SliderState},
+  shared::{
+    utils::{Buffer, ErrorLog},
+    worker::AsyncJob,
+  },
+  state::{SliderInput, SliderLights, SliderState},
 };
 
-use super::config::{LightsMode, ReactiveLayout};
+use super::{
+  config::LightsMode,
+  definition::{AirSide, EffectColor, EffectDefinition},
+  effects::ReactiveGlow,
+  script::LightingScript,
+};
 
 fn get_rainbow(phase: f64, desaturate: bool) -> Rgb<SrgbEncoding, u8> {
   let phase = ((phase % 1.0) + 1.0) % 1.0;
@@ -35,238 +43,135 @@ pub struct LightsJob {
   mode: LightsMode,
   serial_port: Option<Box<dyn SerialPort>>,
   started: Instant,
+  definition: Option<EffectDefinition>,
+  glow: Option<ReactiveGlow>,
+  script: Option<LightingScript>,
   timer: Interval,
+  errors: ErrorLog,
 }
 
 impl LightsJob {
-  pub fn new(state: &SliderState, mode: &LightsMode) -> Self {
+  pub fn new(state: &SliderState, mode: &LightsMode, errors: &ErrorLog) -> Self {
     Self {
       state: state.clone(),
       mode: mode.clone(),
       serial_port: None,
       started: Instant::now(),
+      definition: None,
+      glow: match mode {
+        LightsMode::Reactive { effects, .. } => Some(ReactiveGlow::new(effects)),
+        _ => None,
+      },
+      script: None,
       timer: match match mode {
         LightsMode::Attract { faster, .. } => *faster,
         LightsMode::Websocket { faster, .. } => *faster,
         LightsMode::Serial { faster, .. } => *faster,
+        LightsMode::Script { faster, .. } => *faster,
         _ => false,
       } {
         false => interval(Duration::from_micros(66666)),
         true => interval(Duration::from_micros(33333)),
       },
+      errors: errors.clone(),
     }
   }
 
   fn calc_lights(
-    &self,
+    &mut self,
     flat_input: Option<&Vec<bool>>,
+    input: Option<&SliderInput>,
     serial_buffer: Option<&Buffer>,
     lights: &mut SliderLights,
   ) {
     match &self.mode {
-      LightsMode::Reactive { layout, color, .. } => {
+      LightsMode::Reactive { color, .. } => {
         let flat_input = flat_input.unwrap();
-
-        match layout {
-          ReactiveLayout::Even { splits } => {
-            let buttons_per_split = 32 / splits;
-
-            let banks: Vec<bool> = flat_input
-              .chunks(32 / splits)
-              .take(*splits)
-              .map(|x| x.contains(&true))
-              .collect();
-
-            for idx in 0..31 {
-              lights.paint(
-                idx,
-                match ((idx + 1) % buttons_per_split, (idx + 1) % 2) {
-                  (0, _) => &color.active,
-                  (_, 0) => &color.inactive,
-                  _ => match banks[idx / buttons_per_split] {
-                    true => &color.active,
-                    false => &color.inactive,
-                  },
-                },
+        let definition = self.definition.as_ref().unwrap();
+        let elapsed = self.started.elapsed();
+        let theta = elapsed.div_duration_f64(definition.rainbow_period) % 1.0;
+
+        // Rainbow colors depend on the position across the slider, air LEDs
+        // continue the rainbow outwards on both sides
+        let resolve = |c: &EffectColor, position: f64, current: &[u8]| -> [u8; 3] {
+          match c {
+            EffectColor::Rgb(x) => *x,
+            EffectColor::Active => color.active,
+            EffectColor::Inactive => color.inactive,
+            EffectColor::AirActive => color.air_active,
+            EffectColor::AirInactive => color.air_inactive,
+            EffectColor::Rainbow | EffectColor::RainbowPale => {
+              let rainbow = get_rainbow(
+                theta + position / definition.rainbow_spread,
+                matches!(c, EffectColor::RainbowPale),
               );
+              [rainbow.red, rainbow.green, rainbow.blue]
             }
-
-            for idx in 0..3 {
-              lights.paint_air(
-                idx,
-                match flat_input[32 + idx * 2] || flat_input[33 + idx * 2] {
-                  true => &color.air_active,
-                  false => &color.air_inactive,
-                },
-              )
-            }
+            EffectColor::None => [current[0], current[1], current[2]],
           }
-          ReactiveLayout::Six => {
-            let banks: Vec<bool> = [0..6, 6..10, 10..16, 16..22, 22..26, 26..32]
-              .into_iter()
-              .map(|x| flat_input[x].contains(&true))
-              .collect();
-
-            for idx in (1..31).step_by(2) {
-              lights.paint(
-                idx,
-                match idx {
-                  5 | 9 | 15 | 21 | 25 => &color.active,
-                  _ => &color.inactive,
-                },
-              );
-            }
-
-            for (bank_idxs, bank_val) in [0..6, 6..10, 10..16, 16..22, 22..26, 26..32]
-              .into_iter()
-              .zip(banks)
-            {
-              for idx in bank_idxs.step_by(2) {
-                lights.paint(
-                  idx,
-                  match bank_val {
-                    true => &color.active,
-                    false => &color.inactive,
-                  },
-                )
-              }
-            }
+        };
 
-            for idx in 0..3 {
-              lights.paint_air(
-                idx,
-                match flat_input[32 + idx * 2] || flat_input[33 + idx * 2] {
-                  true => &color.air_active,
-                  false => &color.air_inactive,
-                },
-              )
-            }
+        if let Some(background) = definition.background.as_ref() {
+          for idx in 0..31 {
+            let painted = resolve(background, idx as f64, &lights.ground[idx * 3..]);
+            lights.paint(idx, &painted);
           }
-          ReactiveLayout::Voltex => {
-            lights.ground.fill(0);
-
-            // Fixed
-            lights.paint(3, &[10, 100, 180]);
-            for idx in 0..5 {
-              lights.paint(7 + idx * 4, &[64, 64, 64]);
-            }
-            lights.paint(27, &[180, 10, 110]);
-
-            let voltex_input = VoltexState::from_flat(flat_input);
-
-            // Left laser
-            for (idx, state) in voltex_input.laser[0..2].iter().enumerate() {
-              if *state {
-                lights.paint(0 + idx * 4, &[70, 230, 250]);
-                lights.paint(1 + idx * 4, &[70, 230, 250]);
-                lights.paint(2 + idx * 4, &[70, 230, 250]);
-              }
-            }
-
-            // Right laser
-            for (idx, state) in voltex_input.laser[2..4].iter().enumerate() {
-              if *state {
-                lights.paint(24 + idx * 4, &[250, 60, 200]);
-                lights.paint(25 + idx * 4, &[255, 60, 200]);
-                lights.paint(26 + idx * 4, &[255, 60, 200]);
-              }
-            }
-
-            // Buttons
-            for (idx, state) in voltex_input.bt.iter().enumerate() {
-              if *state {
-                lights.paint(8 + idx * 4, &[255, 255, 255]);
-                lights.paint(10 + idx * 4, &[255, 255, 255]);
-              }
-            }
-
-            // Fx
-            for (idx, state) in voltex_input.fx.iter().enumerate() {
-              if *state {
-                lights.paint(9 + idx * 8, &[250, 100, 30]);
-                lights.paint(11 + idx * 8, &[250, 100, 30]);
-                lights.paint(13 + idx * 8, &[250, 100, 30]);
-              }
-            }
+        }
+        for fixed in definition.fixed.iter() {
+          for idx in fixed.leds.iter() {
+            let painted = resolve(&fixed.color, *idx as f64, &lights.ground[idx * 3..]);
+            lights.paint(*idx, &painted);
           }
-          ReactiveLayout::Hori => {
-            lights.ground.fill(0);
-
-            // Fixed
-            for idx in [7, 15, 23] {
-              lights.paint(idx, &[64, 64, 64]);
-            }
-
-            let hori_state = HoriState::from_flat(flat_input);
-
-            for (idx, (bt, color)) in hori_state
-              .bt
-              .iter()
-              .zip([
-                &[64, 226, 160],
-                &[255, 105, 248],
-                &[124, 178, 232],
-                &[255, 102, 102],
-              ])
-              .enumerate()
-            {
-              let color_f = match bt {
-                true => 1,
-                false => 4,
-              };
-              let adjcolor = [color[0] / color_f, color[1] / color_f, color[2] / color_f];
-              for i in 0..4 {
-                lights.paint(idx * 8 + i * 2, &adjcolor);
-              }
-            }
+        }
 
-            for (idx, (a, b)) in hori_state
-              .slider
-              .iter()
-              .zip(hori_state.slider.iter().skip(1))
-              .enumerate()
-            {
-              if *a || *b {
-                lights.paint(1 + idx * 2, &[200, 200, 200]);
-              }
-            }
+        let mut ground_lit = [false; 31];
+        for bank in definition.banks.iter().filter(|x| x.is_lit(flat_input)) {
+          for idx in bank.leds.iter() {
+            ground_lit[*idx] = true;
           }
-          ReactiveLayout::Rainbow => {
-            let banks: Vec<bool> = flat_input
-              .chunks(2)
-              .take(16)
-              .map(|x| x.contains(&true))
-              .collect();
-            let theta = self
-              .started
-              .elapsed()
-              .div_duration_f64(Duration::from_secs(4))
-              % 1.0;
-
-            for idx in 0..31 {
-              let slice_theta = theta + (idx as f64) / 32.0;
-              let color = get_rainbow(slice_theta, ((idx % 2) == 0) && banks[idx / 2]);
-              lights.paint(idx, &[color.red, color.green, color.blue]);
-            }
+        }
+        let mut air_lit = [false; 3];
+        for bank in definition.air.iter().filter(|x| x.is_lit(flat_input)) {
+          for idx in bank.leds.iter() {
+            air_lit[*idx] = true;
+          }
+        }
+        let glow = self.glow.as_mut().unwrap();
+        glow.update(&ground_lit, &air_lit, elapsed);
+
+        for bank in definition.banks.iter() {
+          for idx in bank.leds.iter() {
+            let current = &lights.ground[idx * 3..];
+            let painted = glow.ground(
+              *idx,
+              &resolve(&bank.active, *idx as f64, current),
+              &resolve(&bank.inactive, *idx as f64, current),
+            );
+            lights.paint(*idx, &painted);
+          }
+        }
 
-            // left
-            for idx in 0..3 {
-              let slice_theta = theta - ((idx + 1) as f64) / 32.0;
-              let color = get_rainbow(
-                slice_theta,
-                flat_input[32 + idx * 2] || flat_input[33 + idx * 2],
+        for bank in definition.air.iter() {
+          for idx in bank.leds.iter() {
+            if bank.side != AirSide::Right {
+              let position = -((*idx + 1) as f64);
+              let current = &lights.air_left[idx * 3..];
+              let painted = glow.air(
+                *idx,
+                &resolve(&bank.active, position, current),
+                &resolve(&bank.inactive, position, current),
               );
-              lights.paint_air_left(idx, &[color.red, color.green, color.blue]);
+              lights.paint_air_left(*idx, &painted);
             }
-
-            // right
-            for idx in 0..3 {
-              let slice_theta = theta + (idx as f64) / 32.0;
-              let color = get_rainbow(
-                slice_theta,
-                flat_input[32 + idx * 2] || flat_input[33 + idx * 2],
+            if bank.side != AirSide::Left {
+              let position = *idx as f64;
+              let current = &lights.air_right[idx * 3..];
+              let painted = glow.air(
+                *idx,
+                &resolve(&bank.active, position, current),
+                &resolve(&bank.inactive, position, current),
               );
-              lights.paint_air_right(idx, &[color.red, color.green, color.blue]);
+              lights.paint_air_right(*idx, &painted);
             }
           }
         }
@@ -298,6 +203,17 @@ impl LightsJob {
           lights.paint_air_right(idx, &[color.red, color.green, color.blue]);
         }
       }
+      LightsMode::Script { .. } => {
+        if let Some(script) = self.script.as_mut() {
+          script.run(
+            input.unwrap(),
+            flat_input.unwrap(),
+            self.started.elapsed(),
+            lights,
+            &self.errors,
+          );
+        }
+      }
       LightsMode::Serial { .. } => {
         // https://github.com/jmontineri/OpeNITHM/blob/89e9a43f7484e8949cd31bbff79c32f21ea3ec1d/Firmware/OpeNITHM/SerialProcessor.h
         // https://github.com/jmontineri/OpeNITHM/blob/89e9a43f7484e8949cd31bbff79c32f21ea3ec1d/Firmware/OpeNITHM/SerialProcessor.cpp
@@ -314,6 +230,7 @@ impl LightsJob {
             {
               lights.paint(idx, &[(*buf_chunk)[1], (*buf_chunk)[2], (*buf_chunk)[0]]);
             }
+            lights.last_frame = Some(Instant::now());
           }
         }
       }
@@ -328,6 +245,33 @@ impl LightsJob {
 impl AsyncJob for LightsJob {
   async fn setup(&mut self) -> bool {
     match &self.mode {
+      LightsMode::Reactive { effect, .. } => match EffectDefinition::load(effect) {
+        Ok(definition) => {
+          info!("Lighting effect {} loaded", effect);
+          self.definition = Some(definition);
+          true
+        }
+        Err(e) => {
+          self.errors.push(format!(
+            "Lighting effect {} could not be loaded: {}",
+            effect, e
+          ));
+          false
+        }
+      },
+      LightsMode::Script { script, .. } => match LightingScript::load(script) {
+        Ok(script) => {
+          self.script = Some(script);
+          true
+        }
+        Err(e) => {
+          self.errors.push(format!(
+            "Lighting script {} could not be loaded: {}",
+            script, e
+          ));
+          false
+        }
+      },
       LightsMode::Serial { port, .. } => {
         info!(
           "Serial port for led opening at {} {:?}",
@@ -353,6 +297,7 @@ impl AsyncJob for LightsJob {
 
   async fn tick(&mut self) -> bool {
     let mut flat_input: Option<Vec<bool>> = None;
+    let mut input: Option<SliderInput> = None;
     let mut serial_buffer: Option<Buffer> = None;
 
     // Do the IO here
@@ -361,6 +306,20 @@ impl AsyncJob for LightsJob {
         let input_handle = self.state.input.lock();
         flat_input = Some(input_handle.to_flat(&sensitivity));
       }
+      LightsMode::Script { sensitivity, .. } => {
+        let input_handle = self.state.input.lock();
+        flat_input = Some(input_handle.to_flat(&sensitivity));
+        input = Some(SliderInput {
+          ground: input_handle.ground,
+          air: input_handle.air,
+          extra: input_handle.extra,
+        });
+        drop(input_handle);
+
+        if let Some(script) = self.script.as_mut() {
+          script.reload_if_changed(&self.errors);
+        }
+      }
       LightsMode::Serial { .. } => {
         if let Some(serial_port) = self.serial_port.as_mut() {
           let mut serial_data_avail = serial_port.bytes_to_read().unwrap_or(0);
@@ -387,9 +346,11 @@ impl AsyncJob for LightsJob {
 
     // Then calculate and transfer
     {
-      let mut lights_handle = self.state.lights.lock();
+      let state = self.state.clone();
+      let mut lights_handle = state.lights.lock();
       self.calc_lights(
         flat_input.as_ref(),
+        input.as_ref(),
         serial_buffer.as_ref(),
         lights_handle.deref_mut(),
       );
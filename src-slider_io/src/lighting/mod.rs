@@ -1,4 +1,13 @@
 pub mod config;
 
+pub mod compositor;
+pub mod definition;
+pub mod dmx;
+pub mod effects;
+pub mod idle;
 pub mod lighting;
+pub mod playback;
+pub mod record;
+pub mod recording;
+pub mod script;
 pub mod umgr_websocket;
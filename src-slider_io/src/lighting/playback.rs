@@ -0,0 +1,132 @@
+use async_trait::async_trait;
+use log::info;
+use std::time::Instant;
+use tokio::time::{sleep, Duration};
+
+use crate::{
+  shared::{utils::ErrorLog, worker::AsyncJob},
+  state::SliderState,
+};
+
+use super::recording::{read_recording, recording_path, LightsFrame};
+
+/// Longest wait between ticks, so the worker can stop during long gaps in a
+/// recording.
+const MAX_WAIT: Duration = Duration::from_millis(100);
+
+/// Plays a lighting recording back onto the slider lights. Frames count as
+/// game lights for the idle timeout.
+pub struct PlaybackJob {
+  state: SliderState,
+  file: String,
+  looped: bool,
+  speed: f32,
+  frames: Vec<LightsFrame>,
+  /// Index of the next frame to show.
+  next: usize,
+  start: Instant,
+  errors: ErrorLog,
+}
+
+impl PlaybackJob {
+  pub fn new(
+    state: &SliderState,
+    file: &String,
+    looped: &bool,
+    speed: &f32,
+    errors: &ErrorLog,
+  ) -> Self {
+    Self {
+      state: state.clone(),
+      file: file.clone(),
+      looped: *looped,
+      speed: *speed,
+      frames: vec![],
+      next: 0,
+      start: Instant::now(),
+      errors: errors.clone(),
+    }
+  }
+
+  /// Position in the recording, scaled by the playback speed.
+  fn position(&self) -> Duration {
+    self.start.elapsed().mul_f32(self.speed)
+  }
+}
+
+#[async_trait]
+impl AsyncJob for PlaybackJob {
+  async fn setup(&mut self) -> bool {
+    let frames = recording_path(&self.file).and_then(|path| read_recording(&path));
+    match frames {
+      // Looping a recording with no length would never wait between frames
+      Ok(frames) if matches!(frames.last(), Some(x) if !x.time.is_zero()) => {
+        info!(
+          "Lighting playback of {}, {} frames",
+          self.file,
+          frames.len()
+        );
+        self.frames = frames;
+        self.next = 0;
+        self.start = Instant::now();
+        true
+      }
+      Ok(_) => {
+        self.errors.push(format!(
+          "Lighting playback failed: {} is empty or has no length",
+          self.file
+        ));
+        false
+      }
+      Err(e) => {
+        self.errors.push(format!("Lighting playback failed: {}", e));
+        false
+      }
+    }
+  }
+
+  async fn tick(&mut self) -> bool {
+    if self.next >= self.frames.len() {
+      match self.looped {
+        true => {
+          // Hold the last frame until the end of the recording
+          let end = self.frames.last().unwrap().time;
+          let position = self.position();
+          if position < end {
+            sleep((end - position).div_f32(self.speed).min(MAX_WAIT)).await;
+            return true;
+          }
+          self.next = 0;
+          self.start = Instant::now();
+        }
+        // Hold the last frame
+        false => {
+          sleep(MAX_WAIT).await;
+          return true;
+        }
+      }
+    }
+
+    // Skip frames that were missed, only the latest one is shown
+    let position = self.position();
+    let mut frame = None;
+    while self.next < self.frames.len() && self.frames[self.next].time <= position {
+      frame = Some(&self.frames[self.next]);
+      self.next += 1;
+    }
+
+    if let Some(frame) = frame {
+      let mut lights_handle = self.state.lights.lock();
+      frame.paint(&mut lights_handle);
+      lights_handle.last_frame = Some(Instant::now());
+      lights_handle.dirty = true;
+    }
+
+    if let Some(next) = self.frames.get(self.next) {
+      let wait = (next.time.saturating_sub(position)).div_f32(self.speed);
+      sleep(wait.min(MAX_WAIT)).await;
+    }
+
+    true
+  }
+}
@@ -0,0 +1,133 @@
+use async_trait::async_trait;
+use log::info;
+use std::{path::PathBuf, time::SystemTime};
+use tokio::time::{interval, Duration, Instant, Interval};
+
+use crate::{
+  shared::{utils::ErrorLog, worker::AsyncJob},
+  state::SliderState,
+};
+
+use super::recording::{recording_path, LightsFrame, RecordingWriter};
+
+/// How often buffered frames are written out to the file.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// Records the slider lights to a new file in the lighting recording
+/// directory, one frame every time the colours change.
+pub struct RecordJob {
+  state: SliderState,
+  path: Option<PathBuf>,
+  writer: Option<RecordingWriter>,
+  last_frame: Option<LightsFrame>,
+  start: Instant,
+  last_flush: Instant,
+  timer: Interval,
+  errors: ErrorLog,
+}
+
+impl RecordJob {
+  pub fn new(state: &SliderState, errors: &ErrorLog) -> Self {
+    Self {
+      state: state.clone(),
+      path: None,
+      writer: None,
+      last_frame: None,
+      start: Instant::now(),
+      last_flush: Instant::now(),
+      timer: interval(Duration::from_millis(4)),
+      errors: errors.clone(),
+    }
+  }
+}
+
+#[async_trait]
+impl AsyncJob for RecordJob {
+  async fn setup(&mut self) -> bool {
+    let timestamp = SystemTime::now()
+      .duration_since(SystemTime::UNIX_EPOCH)
+      .map(|x| x.as_secs())
+      .unwrap_or(0);
+    let path = match recording_path(&format!("recording-{}", timestamp)) {
+      Ok(path) => path,
+      Err(e) => {
+        self
+          .errors
+          .push(format!("Lighting recording failed: {}", e));
+        return false;
+      }
+    };
+
+    match RecordingWriter::create(&path) {
+      Ok(writer) => {
+        info!("Lighting recording to {}", path.display());
+        self.writer = Some(writer);
+        self.path = Some(path);
+        self.start = Instant::now();
+        self.last_flush = Instant::now();
+        true
+      }
+      Err(e) => {
+        self.errors.push(format!(
+          "Lighting recording failed: {}: {}",
+          path.display(),
+          e
+        ));
+        false
+      }
+    }
+  }
+
+  async fn tick(&mut self) -> bool {
+    self.timer.tick().await;
+
+    let frame = {
+      let lights_handle = self.state.lights.lock();
+      match &self.last_frame {
+        Some(last_frame) if last_frame.matches(&lights_handle) => None,
+        _ => Some(LightsFrame::from_lights(
+          self.start.elapsed(),
+          &lights_handle,
+        )),
+      }
+    };
+
+    let writer = match self.writer.as_mut() {
+      Some(writer) => writer,
+      None => return true,
+    };
+    let mut res = Ok(());
+    if let Some(frame) = frame {
+      res = writer.write(&frame);
+      self.last_frame = Some(frame);
+    }
+    if res.is_ok() && self.last_flush.elapsed() >= FLUSH_INTERVAL {
+      self.last_flush = Instant::now();
+      res = writer.flush();
+    }
+    if let Err(e) = res {
+      // Stop writing rather than report every frame
+      self.errors.push(format!(
+        "Lighting recording stopped: {}: {}",
+        self.path.as_ref().unwrap().display(),
+        e
+      ));
+      self.writer = None;
+    }
+
+    true
+  }
+}
+
+impl Drop for RecordJob {
+  fn drop(&mut self) {
+    // Repeats the last colours at the end so playback holds them as long as
+    // they were recorded
+    if let (Some(writer), Some(last_frame)) = (self.writer.as_mut(), self.last_frame.as_ref()) {
+      let mut end = last_frame.clone();
+      end.time = self.start.elapsed();
+      writer.write(&end).and_then(|_| writer.flush()).ok();
+      info!("Lighting recording saved");
+    }
+  }
+}
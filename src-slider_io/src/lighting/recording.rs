@@ -0,0 +1,131 @@
+use std::{
+  fs::File,
+  io::{self, BufReader, BufWriter, Read, Write},
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use crate::{state::SliderLights, system};
+
+// Recordings are the magic `SLLR` and a version byte, followed by one record
+// per frame: the little endian u32 milliseconds since the recording started,
+// then the ground, left air and right air pixels as stored in `SliderLights`.
+
+const MAGIC: [u8; 4] = *b"SLLR";
+const VERSION: u8 = 1;
+const FRAME_LEN: usize = 4 + 3 * (31 + 3 + 3);
+
+pub const EXTENSION: &str = "lights";
+
+/// Path of a recording in the lighting recording directory. The extension
+/// is added if the name has none. Names that would leave the directory, such
+/// as absolute paths or `..`, are rejected.
+pub fn recording_path(name: &str) -> Result<PathBuf, String> {
+  let dir = system::get_lighting_recording_dir().ok_or("config directory not found".to_string())?;
+  let path = system::join_config_path(&dir, name).ok_or(format!(
+    "\"{}\" is not a file name in the lighting recording directory",
+    name
+  ))?;
+  Ok(match path.extension() {
+    Some(_) => path,
+    None => path.with_extension(EXTENSION),
+  })
+}
+
+#[derive(Debug, Clone)]
+pub struct LightsFrame {
+  /// Time since the start of the recording.
+  pub time: Duration,
+  pub ground: [u8; 3 * 31],
+  pub air_left: [u8; 3 * 3],
+  pub air_right: [u8; 3 * 3],
+}
+
+impl LightsFrame {
+  pub fn from_lights(time: Duration, lights: &SliderLights) -> Self {
+    Self {
+      time,
+      ground: lights.ground,
+      air_left: lights.air_left,
+      air_right: lights.air_right,
+    }
+  }
+
+  /// Whether the frame shows the same colours as `lights`.
+  pub fn matches(&self, lights: &SliderLights) -> bool {
+    self.ground == lights.ground
+      && self.air_left == lights.air_left
+      && self.air_right == lights.air_right
+  }
+
+  pub fn paint(&self, lights: &mut SliderLights) {
+    lights.ground = self.ground;
+    lights.air_left = self.air_left;
+    lights.air_right = self.air_right;
+  }
+
+  fn serialize(&self) -> [u8; FRAME_LEN] {
+    let mut buf = [0; FRAME_LEN];
+    let time = u32::try_from(self.time.as_millis()).unwrap_or(u32::MAX);
+    buf[0..4].copy_from_slice(&time.to_le_bytes());
+    buf[4..97].copy_from_slice(&self.ground);
+    buf[97..106].copy_from_slice(&self.air_left);
+    buf[106..115].copy_from_slice(&self.air_right);
+    buf
+  }
+
+  fn parse(buf: &[u8; FRAME_LEN]) -> Self {
+    Self {
+      time: Duration::from_millis(u32::from_le_bytes(buf[0..4].try_into().unwrap()) as u64),
+      ground: buf[4..97].try_into().unwrap(),
+      air_left: buf[97..106].try_into().unwrap(),
+      air_right: buf[106..115].try_into().unwrap(),
+    }
+  }
+}
+
+/// Appends frames to a new recording file.
+pub struct RecordingWriter {
+  writer: BufWriter<File>,
+}
+
+impl RecordingWriter {
+  pub fn create(path: &Path) -> io::Result<Self> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&[VERSION])?;
+
+    Ok(Self { writer })
+  }
+
+  pub fn write(&mut self, frame: &LightsFrame) -> io::Result<()> {
+    self.writer.write_all(&frame.serialize())
+  }
+
+  pub fn flush(&mut self) -> io::Result<()> {
+    self.writer.flush()
+  }
+}
+
+/// Reads every frame of a recording. A frame cut short at the end, e.g. when
+/// slidershim was closed while recording, is dropped.
+pub fn read_recording(path: &Path) -> Result<Vec<LightsFrame>, String> {
+  let describe = |e: io::Error| format!("{}: {}", path.display(), e);
+  let mut reader = BufReader::new(File::open(path).map_err(describe)?);
+
+  let mut header = [0; 5];
+  reader.read_exact(&mut header).map_err(describe)?;
+  if header[0..4] != MAGIC || header[4] != VERSION {
+    return Err(format!("{}: not a lighting recording", path.display()));
+  }
+
+  let mut data = vec![];
+  reader.read_to_end(&mut data).map_err(describe)?;
+
+  Ok(
+    data
+      .chunks_exact(FRAME_LEN)
+      .map(|x| LightsFrame::parse(x.try_into().unwrap()))
+      .collect(),
+  )
+}
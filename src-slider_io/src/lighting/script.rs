@@ -0,0 +1,211 @@
+use log::info;
+use palette::{FromColor, Hsv, Srgb};
+use parking_lot::Mutex;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use std::{
+  fs,
+  path::PathBuf,
+  sync::Arc,
+  time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+  shared::utils::ErrorLog,
+  state::{SliderInput, SliderLights},
+  system,
+};
+
+/// Wall clock time a script may spend on one frame.
+const TIME_LIMIT: Duration = Duration::from_millis(10);
+/// How often the script file is checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+fn rgb(r: i64, g: i64, b: i64) -> i64 {
+  (r.clamp(0, 255) << 16) | (g.clamp(0, 255) << 8) | b.clamp(0, 255)
+}
+
+fn hsv(h: f64, s: f64, v: f64) -> i64 {
+  let color = Srgb::from_color(Hsv::new(
+    h.rem_euclid(360.0) as f32,
+    s.clamp(0.0, 1.0) as f32,
+    v.clamp(0.0, 1.0) as f32,
+  ))
+  .into_format::<u8>();
+  rgb(color.red as i64, color.green as i64, color.blue as i64)
+}
+
+fn colors_to_array(data: &[u8]) -> Dynamic {
+  let colors: Array = data
+    .chunks(3)
+    .map(|x| Dynamic::from_int(rgb(x[0] as i64, x[1] as i64, x[2] as i64)))
+    .collect();
+  colors.into()
+}
+
+/// Copies colors returned by the script, anything that is not a color is left
+/// unchanged.
+fn array_to_colors(v: Option<&Dynamic>, data: &mut [u8]) {
+  if let Some(colors) = v.and_then(|x| x.read_lock::<Array>()) {
+    for (color, pixel) in colors.iter().zip(data.chunks_mut(3)) {
+      if let Ok(color) = color.as_int() {
+        pixel.copy_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+      }
+    }
+  }
+}
+
+/// A lighting script in the lighting effect directory. Scripts define
+/// `fn frame(input, time, lights)` which is called every lighting update and
+/// returns the new lights. `this` is a map kept between frames for the
+/// script's own state.
+pub struct LightingScript {
+  engine: Engine,
+  path: PathBuf,
+  ast: AST,
+  memory: Dynamic,
+  modified: Option<SystemTime>,
+  last_check: Instant,
+  deadline: Arc<Mutex<Instant>>,
+  last_error: Option<String>,
+}
+
+impl LightingScript {
+  /// Loads `<name>.rhai` from the lighting effect directory.
+  pub fn load(name: &str) -> Result<Self, String> {
+    let dir = system::get_lighting_effect_dir().ok_or("config directory not found".to_string())?;
+    let path = system::join_config_path(&dir, &format!("{}.rhai", name))
+      .ok_or(format!("\"{}\" is not a script name", name))?;
+
+    let deadline = Arc::new(Mutex::new(Instant::now()));
+    let mut engine = Engine::new();
+    engine
+      .set_max_operations(1_000_000)
+      .set_max_call_levels(32)
+      .set_max_expr_depths(64, 32)
+      .set_max_string_size(4096)
+      .set_max_array_size(4096)
+      .set_max_map_size(1024)
+      .disable_symbol("eval")
+      .on_print(|s| info!("Lighting script: {}", s))
+      .on_debug(|s, _, _| info!("Lighting script: {}", s))
+      .register_fn("rgb", rgb)
+      .register_fn("hsv", hsv);
+
+    // Stop scripts that run past the time limit even if they stay under the
+    // operation limit, e.g. with slow built-in functions
+    let progress_deadline = deadline.clone();
+    engine.on_progress(move |_| match Instant::now() > *progress_deadline.lock() {
+      true => Some("time limit exceeded".into()),
+      false => None,
+    });
+
+    let mut script = Self {
+      engine,
+      path,
+      ast: AST::empty(),
+      memory: Map::new().into(),
+      modified: None,
+      last_check: Instant::now(),
+      deadline,
+      last_error: None,
+    };
+    script.compile()?;
+
+    Ok(script)
+  }
+
+  fn compile(&mut self) -> Result<(), String> {
+    let modified = fs::metadata(&self.path).and_then(|x| x.modified()).ok();
+    let source =
+      fs::read_to_string(&self.path).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+    let ast = self.engine.compile(source).map_err(|e| e.to_string())?;
+
+    self.ast = ast;
+    self.memory = Map::new().into();
+    self.modified = modified;
+    info!("Lighting script {} loaded", self.path.display());
+
+    Ok(())
+  }
+
+  /// Reloads the script if its file changed. A script that fails to compile
+  /// is reported and the previous version keeps running.
+  pub fn reload_if_changed(&mut self, errors: &ErrorLog) {
+    if self.last_check.elapsed() < RELOAD_INTERVAL {
+      return;
+    }
+    self.last_check = Instant::now();
+
+    let modified = fs::metadata(&self.path).and_then(|x| x.modified()).ok();
+    if modified.is_some() && modified != self.modified {
+      if let Err(e) = self.compile() {
+        self.modified = modified;
+        errors.push(format!("Lighting script could not be reloaded: {}", e));
+      }
+    }
+  }
+
+  /// Runs one frame of the script and paints its result onto `lights`.
+  /// Repeated errors are only reported once.
+  pub fn run(
+    &mut self,
+    input: &SliderInput,
+    flat_input: &Vec<bool>,
+    time: Duration,
+    lights: &mut SliderLights,
+    errors: &ErrorLog,
+  ) {
+    let mut input_map = Map::new();
+    let ints = |x: &[u8]| -> Dynamic {
+      let values: Array = x.iter().map(|x| Dynamic::from_int(*x as i64)).collect();
+      values.into()
+    };
+    let touched: Array = flat_input[0..32]
+      .iter()
+      .map(|x| Dynamic::from_bool(*x))
+      .collect();
+    input_map.insert("ground".into(), ints(&input.ground));
+    input_map.insert("air".into(), ints(&input.air));
+    input_map.insert("extra".into(), ints(&input.extra));
+    input_map.insert("touched".into(), touched.into());
+
+    let mut lights_map = Map::new();
+    lights_map.insert("ground".into(), colors_to_array(&lights.ground));
+    lights_map.insert("airLeft".into(), colors_to_array(&lights.air_left));
+    lights_map.insert("airRight".into(), colors_to_array(&lights.air_right));
+
+    *self.deadline.lock() = Instant::now() + TIME_LIMIT;
+    let res = self.engine.call_fn_with_options::<Dynamic>(
+      CallFnOptions::new()
+        .eval_ast(false)
+        .bind_this_ptr(&mut self.memory),
+      &mut Scope::new(),
+      &self.ast,
+      "frame",
+      (input_map, time.as_secs_f64(), lights_map),
+    );
+
+    match res {
+      Ok(res) => {
+        if let Some(res) = res.read_lock::<Map>() {
+          array_to_colors(res.get("ground"), &mut lights.ground);
+          array_to_colors(res.get("airLeft"), &mut lights.air_left);
+          array_to_colors(res.get("airRight"), &mut lights.air_right);
+        }
+        self.last_error = None;
+      }
+      Err(e) => {
+        let e = match *e {
+          EvalAltResult::ErrorTerminated(..) => {
+            format!("frame took longer than {}ms", TIME_LIMIT.as_millis())
+          }
+          e => e.to_string(),
+        };
+        if self.last_error.as_ref() != Some(&e) {
+          errors.push(format!("Lighting script error: {}", e));
+          self.last_error = Some(e);
+        }
+      }
+    }
+  }
+}
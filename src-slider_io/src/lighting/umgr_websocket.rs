@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use futures::{SinkExt, StreamExt};
+use futures::{future, FutureExt, SinkExt, StreamExt};
 use hyper::{
   header,
   server::conn::AddrStream,
@@ -8,7 +8,11 @@ use hyper::{
   Body, Method, Request, Response, Server, StatusCode,
 };
 use log::{error, info};
-use std::{convert::Infallible, future::Future, net::SocketAddr};
+use std::{
+  convert::Infallible,
+  future::Future,
+  net::{IpAddr, SocketAddr},
+};
 use tokio::{
   select,
   sync::mpsc,
@@ -17,7 +21,10 @@ use tokio::{
 use tokio_tungstenite::WebSocketStream;
 use tungstenite::{handshake, Message};
 
-use crate::{shared::worker::AsyncHaltableJob, state::SliderState};
+use crate::{
+  shared::{net, utils::ErrorLog, worker::AsyncHaltableJob},
+  state::SliderState,
+};
 
 async fn error_response() -> Result<Response<Body>, Infallible> {
   Ok(
@@ -114,6 +121,7 @@ async fn handle_umgr_leds(ws_stream: WebSocketStream<Upgraded>, state: SliderSta
                       .paint_air(2 - i, &[payload[pos], payload[pos + 1], payload[pos + 2]]);
                   }
 
+                  lights_handle.last_frame = Some(Instant::now().into_std());
                   if latest_lights.elapsed() > delay {
                     lights_handle.dirty = true;
                     latest_lights = Instant::now();
@@ -271,14 +279,24 @@ pub struct UmgrWebsocketJob {
   state: SliderState,
   faster: bool,
   port: u16,
+  bind: Vec<IpAddr>,
+  errors: ErrorLog,
 }
 
 impl UmgrWebsocketJob {
-  pub fn new(state: &SliderState, faster: &bool, port: &u16) -> Self {
+  pub fn new(
+    state: &SliderState,
+    faster: &bool,
+    port: &u16,
+    bind: &Vec<IpAddr>,
+    errors: &ErrorLog,
+  ) -> Self {
     Self {
       state: state.clone(),
       faster: *faster,
       port: *port,
+      bind: bind.clone(),
+      errors: errors.clone(),
     }
   }
 }
@@ -299,15 +317,42 @@ impl AsyncHaltableJob for UmgrWebsocketJob {
       }
     });
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], self.port));
-    info!("UMGR LED websocket server listening on {}", addr);
+    let stop_signal = stop_signal.shared();
+    let mut servers = vec![];
+    for addr in self.bind.iter().map(|ip| SocketAddr::new(*ip, self.port)) {
+      let builder = match net::bind_tcp(&addr) {
+        Ok(listener) => match Server::from_tcp(listener) {
+          Ok(builder) => builder,
+          Err(e) => {
+            self.errors.push(format!(
+              "UMGR LED websocket server cannot listen on {}: {}",
+              addr, e
+            ));
+            continue;
+          }
+        },
+        Err(e) => {
+          self.errors.push(net::describe_bind_error(
+            "UMGR LED websocket server",
+            &addr,
+            &e,
+          ));
+          continue;
+        }
+      };
+      info!("UMGR LED websocket server listening on {}", addr);
 
-    let server = Server::bind(&addr)
-      .serve(make_svc)
-      .with_graceful_shutdown(stop_signal);
+      servers.push(
+        builder
+          .serve(make_svc.clone())
+          .with_graceful_shutdown(stop_signal.clone()),
+      );
+    }
 
-    if let Err(e) = server.await {
-      info!("UMGR LED websocket server stopped: {}", e);
+    for res in future::join_all(servers).await {
+      if let Err(e) = res {
+        info!("UMGR LED websocket server stopped: {}", e);
+      }
     }
   }
 }
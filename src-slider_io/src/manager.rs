@@ -9,7 +9,12 @@ use tokio::{
   sync::{mpsc, oneshot},
 };
 
-use crate::{config::Config, context::Context, state::SliderState};
+use crate::{
+  config::Config,
+  context::Context,
+  device::{brokenithm_metrics::BrokenithmClientStatus, config::BrokenithmConfig},
+  state::SliderState,
+};
 
 pub struct Manager {
   state: Arc<Mutex<Option<SliderState>>>,
@@ -81,6 +86,13 @@ impl Manager {
     self.tx_config.send(config).unwrap();
   }
 
+  pub fn update_brokenithm_config(&self, brokenithm_config: BrokenithmConfig) {
+    let context_handle = self.context.lock();
+    if let Some(context) = context_handle.as_ref() {
+      context.update_brokenithm_config(brokenithm_config);
+    }
+  }
+
   pub fn try_get_state(&self) -> Option<SliderState> {
     let state_handle = self.state.lock();
     state_handle.as_ref().map(|x| x.clone())
@@ -93,6 +105,22 @@ impl Manager {
       .map(|context| context.timer_state())
       .unwrap_or("".to_string())
   }
+
+  pub fn get_error_state(&self) -> String {
+    let context_handle = self.context.lock();
+    context_handle
+      .as_ref()
+      .map(|context| context.error_state())
+      .unwrap_or("".to_string())
+  }
+
+  pub fn get_brokenithm_status(&self) -> Vec<BrokenithmClientStatus> {
+    let context_handle = self.context.lock();
+    context_handle
+      .as_ref()
+      .map(|context| context.brokenithm_status())
+      .unwrap_or_default()
+  }
 }
 
 impl Drop for Manager {
@@ -1,5 +1,11 @@
 use serde_json::Value;
 
+use crate::shared::{
+  dmx::{DmxProtocol, MAX_START_CHANNEL},
+  remote::RemoteTransport,
+  wled::{PixelLayout, WledProtocol, MAX_PIXELS},
+};
+
 #[derive(Debug, Clone, Copy)]
 pub enum PollingRate {
   Sixty,
@@ -57,6 +63,58 @@ pub enum OutputMode {
     url: String,
     polling: PollingRate,
   },
+  Network {
+    transport: RemoteTransport,
+    addr: String,
+    polling: PollingRate,
+  },
+  Mouse {
+    relative: bool,
+    speed: u16,
+    smoothing: u8,
+    polling: PollingRate,
+    sensitivity: u8,
+  },
+  Osc {
+    addr: String,
+    polling: PollingRate,
+    sensitivity: u8,
+  },
+  Midi {
+    port: String,
+    channel: u8,
+    layout: String,
+    polling: PollingRate,
+    sensitivity: u8,
+  },
+  Dmx {
+    protocol: DmxProtocol,
+    /// Empty for the protocol's default multicast or broadcast address.
+    addr: String,
+    universe: u16,
+    /// First channel of the lights, counting from 1.
+    start_channel: usize,
+    /// Packets per second.
+    rate: u8,
+  },
+  OpenRgb {
+    addr: String,
+    /// Part of the device name, the first device if empty.
+    device: String,
+    /// Part of the zone name, every LED of the device if empty.
+    zone: String,
+  },
+  Wled {
+    protocol: WledProtocol,
+    addr: String,
+    /// Pixels showing the ground lights.
+    pixels: usize,
+    /// Pixels showing each side of the air lights, after the ground pixels.
+    air_pixels: usize,
+    layout: PixelLayout,
+    /// Frames per second.
+    rate: u8,
+  },
 }
 
 impl PollingRate {
@@ -83,6 +141,28 @@ impl PollingRate {
 }
 
 impl OutputMode {
+  /// Parses the main output followed by every entry of `extraOutputs`. Extra
+  /// entries only need the keys that differ from the main output, e.g.
+  /// `{"outputMode": "osc", "outputPolling": "60"}`.
+  pub fn list_from_serde_value(v: &Value) -> Option<Vec<Self>> {
+    let mut modes = vec![Self::from_serde_value(v)?];
+
+    if let Value::Array(extra_outputs) = &v["extraOutputs"] {
+      for extra_output in extra_outputs {
+        let mut merged = v.clone();
+        // Empty fields in the UI are null, those fall back to the main output
+        for (key, value) in extra_output.as_object()? {
+          if !value.is_null() {
+            merged[key] = value.clone();
+          }
+        }
+        modes.push(Self::from_serde_value(&merged)?);
+      }
+    }
+
+    Some(modes)
+  }
+
   pub fn from_serde_value(v: &Value) -> Option<Self> {
     Some(match v["outputMode"].as_str().unwrap() {
       "none" => OutputMode::None,
@@ -164,6 +244,83 @@ impl OutputMode {
         url: v["outputWebsocketUrl"].as_str()?.to_string(),
         polling: PollingRate::from_str(v["outputPolling"].as_str()?)?,
       },
+      "network-udp" => OutputMode::Network {
+        transport: RemoteTransport::Udp,
+        addr: v["outputNetworkAddr"].as_str()?.to_string(),
+        polling: PollingRate::from_str(v["outputPolling"].as_str()?)?,
+      },
+      "network-tcp" => OutputMode::Network {
+        transport: RemoteTransport::Tcp,
+        addr: v["outputNetworkAddr"].as_str()?.to_string(),
+        polling: PollingRate::from_str(v["outputPolling"].as_str()?)?,
+      },
+      "mouse-absolute" => OutputMode::Mouse {
+        relative: false,
+        speed: u16::try_from(v["mouseSpeed"].as_i64().unwrap_or(1000)).ok()?,
+        smoothing: u8::try_from(v["mouseSmoothing"].as_i64().unwrap_or(50)).ok()?,
+        polling: PollingRate::from_str(v["outputPolling"].as_str()?)?,
+        sensitivity: u8::try_from(v["keyboardSensitivity"].as_i64()?).ok()?,
+      },
+      "mouse-relative" => OutputMode::Mouse {
+        relative: true,
+        speed: u16::try_from(v["mouseSpeed"].as_i64().unwrap_or(1000)).ok()?,
+        smoothing: u8::try_from(v["mouseSmoothing"].as_i64().unwrap_or(50)).ok()?,
+        polling: PollingRate::from_str(v["outputPolling"].as_str()?)?,
+        sensitivity: u8::try_from(v["keyboardSensitivity"].as_i64()?).ok()?,
+      },
+      "osc" => OutputMode::Osc {
+        addr: v["outputOscAddr"].as_str()?.to_string(),
+        polling: PollingRate::from_str(v["outputPolling"].as_str()?)?,
+        sensitivity: u8::try_from(v["keyboardSensitivity"].as_i64()?).ok()?,
+      },
+      "midi" => OutputMode::Midi {
+        port: v["midiOutPort"].as_str().unwrap_or("").to_string(),
+        channel: u8::try_from(v["midiOutChannel"].as_i64().unwrap_or(1)).ok()?,
+        layout: v["midiOutLayout"].as_str().unwrap_or("default").to_string(),
+        polling: PollingRate::from_str(v["outputPolling"].as_str()?)?,
+        sensitivity: u8::try_from(v["keyboardSensitivity"].as_i64()?).ok()?,
+      },
+      "openrgb" => OutputMode::OpenRgb {
+        addr: v["outputOpenRgbAddr"]
+          .as_str()
+          .unwrap_or("127.0.0.1:6742")
+          .to_string(),
+        device: v["outputOpenRgbDevice"].as_str().unwrap_or("").to_string(),
+        zone: v["outputOpenRgbZone"].as_str().unwrap_or("").to_string(),
+      },
+      x if x.starts_with("wled-") => OutputMode::Wled {
+        protocol: WledProtocol::from_str(&x[5..])?,
+        addr: v["outputWledAddr"].as_str().unwrap_or("").to_string(),
+        pixels: match v["outputWledPixels"].as_u64().unwrap_or(60) as usize {
+          x @ 1..=MAX_PIXELS => x,
+          _ => return None,
+        },
+        air_pixels: match v["outputWledAirPixels"].as_u64().unwrap_or(0) as usize {
+          x @ 0..=MAX_PIXELS => x,
+          _ => return None,
+        },
+        layout: PixelLayout::from_str(v["outputWledLayout"].as_str().unwrap_or("stretch"))?,
+        rate: match v["outputWledRate"].as_u64().unwrap_or(30) {
+          x @ 1..=60 => x as u8,
+          _ => return None,
+        },
+      },
+      x if x.starts_with("dmx-") => {
+        let protocol = DmxProtocol::from_str(&x[4..])?;
+        OutputMode::Dmx {
+          protocol,
+          addr: v["outputDmxAddr"].as_str().unwrap_or("").to_string(),
+          universe: protocol.parse_universe(&v["outputDmxUniverse"])?,
+          start_channel: match v["outputDmxStartChannel"].as_u64()? as usize {
+            x @ 1..=MAX_START_CHANNEL => x,
+            _ => return None,
+          },
+          rate: match v["outputDmxRate"].as_u64().unwrap_or(30) {
+            x @ 1..=60 => x as u8,
+            _ => return None,
+          },
+        }
+      }
       _ => return None,
     })
   }
@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+use log::info;
+use tokio::{
+  net::UdpSocket,
+  time::{interval, Duration, Interval},
+};
+
+use crate::{
+  shared::{
+    dmx::{lights_to_channels, DmxProtocol},
+    net,
+    utils::ErrorLog,
+    worker::AsyncJob,
+  },
+  state::SliderState,
+};
+
+/// Streams the slider lights to a DMX universe over sACN or Art-Net. Packets
+/// are sent at a steady rate even if lights do not change, as receivers
+/// treat a silent universe as lost.
+pub struct DmxOutputJob {
+  state: SliderState,
+  protocol: DmxProtocol,
+  addr: String,
+  universe: u16,
+  start_channel: usize,
+  timer: Interval,
+  socket: Option<UdpSocket>,
+  seq: u8,
+  channels: [u8; 512],
+  errors: ErrorLog,
+}
+
+impl DmxOutputJob {
+  pub fn new(
+    state: &SliderState,
+    protocol: &DmxProtocol,
+    addr: &String,
+    universe: &u16,
+    start_channel: &usize,
+    rate: &u8,
+    errors: &ErrorLog,
+  ) -> Self {
+    Self {
+      state: state.clone(),
+      protocol: *protocol,
+      addr: addr.clone(),
+      universe: *universe,
+      start_channel: *start_channel,
+      timer: interval(Duration::from_secs(1) / *rate as u32),
+      socket: None,
+      seq: 0,
+      channels: [0; 512],
+      errors: errors.clone(),
+    }
+  }
+
+  /// The configured address. sACN defaults to the universe's multicast group
+  /// and Art-Net to broadcast.
+  fn target(&self) -> String {
+    match self.addr.trim() {
+      "" => match self.protocol {
+        DmxProtocol::Sacn => format!("239.255.{}.{}", self.universe >> 8, self.universe & 0xff),
+        DmxProtocol::ArtNet => "255.255.255.255".to_string(),
+      },
+      x => x.to_string(),
+    }
+  }
+}
+
+#[async_trait]
+impl AsyncJob for DmxOutputJob {
+  async fn setup(&mut self) -> bool {
+    let target = self.target();
+    match net::connect_udp("DMX output", &target, self.protocol.port()).await {
+      Ok(socket) => {
+        info!(
+          "DMX output sending {:?} universe {} to {}",
+          self.protocol, self.universe, target
+        );
+        self.socket = Some(socket);
+        true
+      }
+      Err(e) => {
+        self.errors.push(e);
+        false
+      }
+    }
+  }
+
+  async fn tick(&mut self) -> bool {
+    self.timer.tick().await;
+
+    {
+      let lights_handle = self.state.lights.lock();
+      lights_to_channels(
+        &lights_handle.output(),
+        self.start_channel,
+        &mut self.channels,
+      );
+    }
+
+    // A sequence of 0 turns off reordering on Art-Net receivers, so skip it
+    self.seq = match self.seq.wrapping_add(1) {
+      0 => 1,
+      x => x,
+    };
+    let packet = self
+      .protocol
+      .serialize(self.universe, self.seq, &self.channels);
+
+    if let Some(socket) = self.socket.as_ref() {
+      socket.send(&packet).await.ok();
+    }
+
+    true
+  }
+}
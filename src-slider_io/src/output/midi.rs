@@ -0,0 +1,162 @@
+use log::{error, info};
+use midir::{MidiOutput as MidirOutput, MidiOutputConnection};
+
+use crate::{
+  device::{
+    input_map::InputTarget,
+    midi::{load_midi_map, MidiControl},
+  },
+  state::SliderState,
+};
+
+use super::output::OutputHandler;
+
+/// Plays the slider on a MIDI port. Uses the same layout files as the MIDI
+/// input, every note or control is driven by the pads mapped to it.
+pub struct MidiOutput {
+  state: SliderState,
+  connection: MidiOutputConnection,
+  channel: u8,
+  controls: Vec<(MidiControl, Vec<InputTarget>)>,
+  values: Vec<u8>,
+}
+
+impl MidiOutput {
+  /// Opens the first port with `port` in its name. Outside of Windows an
+  /// empty name creates a virtual port instead.
+  pub fn new(state: &SliderState, port: &str, channel: u8, layout: &str) -> Result<Self, String> {
+    let midi_map = load_midi_map(layout)
+      .map_err(|e| format!("MIDI layout {} could not be loaded: {}", layout, e))?;
+    let controls: Vec<(MidiControl, Vec<InputTarget>)> = midi_map
+      .targets()
+      .map(|(control, targets)| (*control, targets.clone()))
+      .collect();
+
+    let midi_out =
+      MidirOutput::new("slidershim").map_err(|e| format!("MIDI output error: {}", e))?;
+    let ports = midi_out.ports();
+    for port in ports.iter() {
+      info!(
+        "MIDI output found port {}",
+        midi_out.port_name(port).unwrap_or_default()
+      );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    if port.is_empty() {
+      use midir::os::unix::VirtualOutput;
+
+      let connection = midi_out
+        .create_virtual("slidershim")
+        .map_err(|e| format!("MIDI output virtual port could not be created: {}", e))?;
+      info!("MIDI output created virtual port slidershim");
+
+      return Ok(Self::from_connection(state, connection, channel, controls));
+    }
+
+    let needle = port.to_lowercase();
+    let port = ports
+      .iter()
+      .find(|x| {
+        midi_out
+          .port_name(x)
+          .map(|name| name.to_lowercase().contains(&needle))
+          .unwrap_or(false)
+      })
+      .ok_or(format!("MIDI output port \"{}\" not found", port))?;
+    let port_name = midi_out.port_name(port).unwrap_or_default();
+
+    let connection = midi_out
+      .connect(port, "slidershim-out")
+      .map_err(|e| format!("MIDI output port {} could not be opened: {}", port_name, e))?;
+    info!("MIDI output connected to {}", port_name);
+
+    Ok(Self::from_connection(state, connection, channel, controls))
+  }
+
+  fn from_connection(
+    state: &SliderState,
+    connection: MidiOutputConnection,
+    channel: u8,
+    controls: Vec<(MidiControl, Vec<InputTarget>)>,
+  ) -> Self {
+    Self {
+      state: state.clone(),
+      connection,
+      // Channels are 1-16 for users and 0-15 on the wire
+      channel: channel.clamp(1, 16) - 1,
+      values: vec![0; controls.len()],
+      controls,
+    }
+  }
+
+  fn send(&mut self, control: MidiControl, value: u8) {
+    let message = match control {
+      MidiControl::Note(note) if value > 0 => [0x90 | self.channel, note, value],
+      MidiControl::Note(note) => [0x80 | self.channel, note, 0],
+      MidiControl::Cc(cc) => [0xb0 | self.channel, cc, value],
+    };
+    if let Err(e) = self.connection.send(&message) {
+      error!("MIDI output send error: {}", e);
+    }
+  }
+}
+
+impl OutputHandler for MidiOutput {
+  fn tick(&mut self, flat_input: &Vec<bool>) -> bool {
+    // Ground pads give their pressure as velocity, air and extra are on or off
+    let next_values: Vec<u8> = {
+      let input_handle = self.state.input.lock();
+      self
+        .controls
+        .iter()
+        .map(|(_, targets)| {
+          targets
+            .iter()
+            .map(|target| match *target {
+              InputTarget::Ground(idx) if flat_input[idx] => (input_handle.ground[idx] / 2).max(1),
+              InputTarget::Air(idx) if flat_input[32 + idx] => 127,
+              InputTarget::Extra(idx) if flat_input[38 + idx] => 127,
+              _ => 0,
+            })
+            .max()
+            .unwrap_or(0)
+        })
+        .collect()
+    };
+
+    let mut changed = false;
+    for i in 0..self.controls.len() {
+      let (control, value) = (self.controls[i].0, next_values[i]);
+      let last = self.values[i];
+
+      // Notes only change on press and release, controls follow the value
+      let send = match control {
+        MidiControl::Note(_) => (last > 0) != (value > 0),
+        MidiControl::Cc(_) => last != value,
+      };
+      if send {
+        self.send(control, value);
+        self.values[i] = value;
+        changed = true;
+      }
+    }
+
+    changed
+  }
+
+  fn reset(&mut self) {
+    for i in 0..self.controls.len() {
+      if self.values[i] > 0 {
+        self.send(self.controls[i].0, 0);
+        self.values[i] = 0;
+      }
+    }
+  }
+}
+
+impl Drop for MidiOutput {
+  fn drop(&mut self) {
+    self.reset();
+  }
+}
@@ -4,4 +4,12 @@ mod gamepad;
 mod hori;
 mod keyboard;
 
+pub mod dmx;
+pub mod midi;
+pub mod mouse;
+pub mod network;
+pub mod openrgb;
+pub mod osc;
+pub mod wled;
+
 pub mod output;
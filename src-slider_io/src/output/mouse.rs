@@ -0,0 +1,184 @@
+use std::mem;
+use winapi::{
+  ctypes::c_int,
+  shared::windef::POINT,
+  um::winuser::{
+    GetCursorPos, GetSystemMetrics, SendInput, INPUT, INPUT_MOUSE, MOUSEEVENTF_ABSOLUTE,
+    MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP,
+    MOUSEEVENTF_MOVE, MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP, MOUSEINPUT, SM_CYSCREEN,
+  },
+};
+
+use super::output::OutputHandler;
+
+const BUTTON_FLAGS: [(u32, u32); 3] = [
+  (MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP),
+  (MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP),
+  (MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP),
+];
+
+/// Uses the slider as a touch strip. The touch position moves the cursor
+/// horizontally, either across the whole screen or like a touchpad. Air
+/// strings and the first extra button are the left button, the other extra
+/// buttons are the right and middle buttons.
+pub struct MouseOutput {
+  relative: bool,
+  speed: f32,
+  smoothing: f32,
+
+  /// Smoothed touch position from 0 (left) to 1 (right).
+  pos: Option<f32>,
+  last_buttons: [bool; 3],
+}
+
+impl MouseOutput {
+  /// `speed` is the cursor travel in pixels for a swipe across the slider in
+  /// relative mode. `smoothing` is 0-100, higher is smoother but lags more.
+  pub fn new(relative: bool, speed: u16, smoothing: u8) -> Self {
+    Self {
+      relative,
+      speed: speed as f32,
+      smoothing: (smoothing.min(95) as f32) / 100.0,
+
+      pos: None,
+      last_buttons: [false; 3],
+    }
+  }
+
+  /// Movement to a new touch position, the vertical position is kept.
+  fn move_input(&self, next_pos: f32) -> MOUSEINPUT {
+    match self.relative {
+      true => {
+        let pos = self.pos.unwrap_or(next_pos);
+        let dx = (next_pos * self.speed).round() - (pos * self.speed).round();
+        mouse_input(dx as i32, 0, MOUSEEVENTF_MOVE)
+      }
+      false => {
+        // Absolute coordinates are 0-65535 across the primary screen
+        let mut cursor = POINT { x: 0, y: 0 };
+        let height = unsafe {
+          GetCursorPos(&mut cursor);
+          GetSystemMetrics(SM_CYSCREEN).max(2)
+        };
+        let x = (next_pos * 65535.0).round() as i32;
+        let y = (cursor.y.clamp(0, height - 1) as i64 * 65535 / (height - 1) as i64) as i32;
+        mouse_input(x, y, MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE)
+      }
+    }
+  }
+
+  fn send(&self, inputs: &Vec<MOUSEINPUT>) {
+    if inputs.is_empty() {
+      return;
+    }
+
+    let mut buf: Vec<INPUT> = inputs
+      .iter()
+      .map(|mi| {
+        let mut input = INPUT {
+          type_: INPUT_MOUSE,
+          u: unsafe { mem::zeroed() },
+        };
+        unsafe {
+          *input.u.mi_mut() = *mi;
+        }
+        input
+      })
+      .collect();
+
+    unsafe {
+      SendInput(
+        buf.len() as u32,
+        buf.as_mut_ptr(),
+        mem::size_of::<INPUT>() as c_int,
+      );
+    }
+  }
+}
+
+fn mouse_input(dx: i32, dy: i32, flags: u32) -> MOUSEINPUT {
+  MOUSEINPUT {
+    dx,
+    dy,
+    mouseData: 0,
+    dwFlags: flags,
+    time: 0,
+    dwExtraInfo: 0,
+  }
+}
+
+impl OutputHandler for MouseOutput {
+  fn tick(&mut self, flat_input: &Vec<bool>) -> bool {
+    let mut inputs = vec![];
+
+    // Touch position is the middle of all touched columns
+    let columns: Vec<usize> = (0..16)
+      .filter(|i| flat_input[i * 2] || flat_input[i * 2 + 1])
+      .collect();
+    let touch = match columns.len() {
+      0 => None,
+      n => Some((columns.iter().sum::<usize>() as f32 / n as f32 + 0.5) / 16.0),
+    };
+
+    match (touch, self.pos) {
+      (Some(touch), Some(pos)) => {
+        let next_pos = pos + (touch - pos) * (1.0 - self.smoothing);
+        let (last_x, next_x) = match self.relative {
+          true => (pos * self.speed, next_pos * self.speed),
+          false => (pos * 65535.0, next_pos * 65535.0),
+        };
+        if next_x.round() != last_x.round() {
+          inputs.push(self.move_input(next_pos));
+        }
+        self.pos = Some(next_pos);
+      }
+      // A new touch starts where the finger is, only absolute mode jumps
+      (Some(touch), None) => {
+        if !self.relative {
+          inputs.push(self.move_input(touch));
+        }
+        self.pos = Some(touch);
+      }
+      (None, _) => self.pos = None,
+    }
+
+    let air = flat_input[32..38].iter().any(|x| *x);
+    let buttons = [air || flat_input[38], flat_input[39], flat_input[40]];
+    for (i, (next, last)) in buttons.iter().zip(self.last_buttons.iter()).enumerate() {
+      if next != last {
+        let (down, up) = BUTTON_FLAGS[i];
+        inputs.push(mouse_input(
+          0,
+          0,
+          match *next {
+            true => down,
+            false => up,
+          },
+        ));
+      }
+    }
+    self.last_buttons = buttons;
+
+    self.send(&inputs);
+    true
+  }
+
+  fn reset(&mut self) {
+    let mut inputs = vec![];
+    for (i, last) in self.last_buttons.iter().enumerate() {
+      if *last {
+        inputs.push(mouse_input(0, 0, BUTTON_FLAGS[i].1));
+      }
+    }
+    self.last_buttons = [false; 3];
+    self.pos = None;
+
+    self.send(&inputs);
+  }
+}
+
+impl Drop for MouseOutput {
+  fn drop(&mut self) {
+    self.reset();
+  }
+}
@@ -0,0 +1,235 @@
+use async_trait::async_trait;
+use log::{error, info};
+use std::sync::Arc;
+use tokio::{
+  io::{AsyncReadExt, AsyncWriteExt},
+  net::{lookup_host, tcp::OwnedWriteHalf, TcpStream, UdpSocket},
+  task::JoinHandle,
+  time::{interval, Duration, Instant, Interval},
+};
+
+use crate::{
+  shared::{
+    net,
+    remote::{RemoteFrame, RemoteKind, RemoteTransport, SeqTracker, DEFAULT_PORT, MAX_FRAME_LEN},
+    worker::AsyncJob,
+  },
+  state::{SliderInput, SliderState},
+};
+
+use super::config::PollingRate;
+
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(1000);
+
+enum Link {
+  Udp(Arc<UdpSocket>),
+  Tcp(OwnedWriteHalf),
+}
+
+/// Streams local input to a remote slidershim running the network input
+/// device, and applies the lights it sends back to the local state.
+pub struct NetworkOutputJob {
+  state: SliderState,
+  transport: RemoteTransport,
+  addr: String,
+  timer: Interval,
+  seq: u32,
+  link: Option<Link>,
+  reader: Option<JoinHandle<()>>,
+  last_connect: Option<Instant>,
+}
+
+impl NetworkOutputJob {
+  pub fn new(
+    state: &SliderState,
+    transport: &RemoteTransport,
+    addr: &String,
+    polling: &PollingRate,
+  ) -> Self {
+    Self {
+      state: state.clone(),
+      transport: *transport,
+      addr: addr.clone(),
+      timer: interval(Duration::from_micros(polling.to_t_u64())),
+      seq: 0,
+      link: None,
+      reader: None,
+      last_connect: None,
+    }
+  }
+
+  fn next_seq(&mut self) -> u32 {
+    let seq = self.seq;
+    self.seq = self.seq.wrapping_add(1);
+    seq
+  }
+
+  async fn connect(&mut self) {
+    if let Some(last_connect) = self.last_connect {
+      if last_connect.elapsed() < RECONNECT_INTERVAL {
+        return;
+      }
+    }
+    self.last_connect = Some(Instant::now());
+
+    let hello = RemoteFrame::hello(self.next_seq()).serialize();
+    let state = self.state.clone();
+    match self.transport {
+      RemoteTransport::Udp => {
+        // Retried every second, so errors are only logged
+        let socket = match net::connect_udp("Network output", &self.addr, DEFAULT_PORT).await {
+          Ok(socket) => socket,
+          Err(e) => {
+            error!("{}", e);
+            return;
+          }
+        };
+        socket.send(&hello).await.ok();
+        info!("Network output sending to udp {}", self.addr);
+
+        let socket = Arc::new(socket);
+        let read_socket = Arc::clone(&socket);
+        self.reader = Some(tokio::spawn(async move {
+          let mut buf = [0; MAX_FRAME_LEN];
+          let mut tracker = SeqTracker::new();
+          loop {
+            // Errors are usually the remote not listening yet, keep waiting
+            if let Ok(len) = read_socket.recv(&mut buf).await {
+              if let Ok(Some((frame, _))) = RemoteFrame::parse(&buf[..len]) {
+                apply_lights(&frame, &mut tracker, &state);
+              }
+            }
+          }
+        }));
+        self.link = Some(Link::Udp(socket));
+      }
+      RemoteTransport::Tcp => {
+        let target = net::with_default_port(&self.addr, DEFAULT_PORT);
+        let addr = match lookup_host(target.as_str())
+          .await
+          .ok()
+          .and_then(|mut x| x.next())
+        {
+          Some(addr) => addr,
+          None => {
+            error!("Network output cannot resolve {}", target);
+            return;
+          }
+        };
+        let stream = match TcpStream::connect(addr).await {
+          Ok(stream) => stream,
+          Err(e) => {
+            error!("Network output cannot connect to {}: {}", addr, e);
+            return;
+          }
+        };
+        stream.set_nodelay(true).ok();
+        let (mut read_half, mut write_half) = stream.into_split();
+        if write_half.write_all(&hello).await.is_err() {
+          return;
+        }
+        info!("Network output connected to tcp {}", addr);
+
+        self.reader = Some(tokio::spawn(async move {
+          let mut pending: Vec<u8> = vec![];
+          let mut buf = [0; 1024];
+          let mut tracker = SeqTracker::new();
+          loop {
+            match read_half.read(&mut buf).await {
+              Ok(0) | Err(_) => break,
+              Ok(len) => pending.extend(&buf[..len]),
+            }
+            let mut used = 0;
+            while let Ok(Some((frame, len))) = RemoteFrame::parse(&pending[used..]) {
+              apply_lights(&frame, &mut tracker, &state);
+              used += len;
+            }
+            pending.drain(..used);
+          }
+          info!(
+            "Network output lights received:{} lost:{} stale:{}",
+            tracker.received, tracker.lost, tracker.stale
+          );
+        }));
+        self.link = Some(Link::Tcp(write_half));
+      }
+    }
+  }
+
+  fn disconnect(&mut self) {
+    self.link = None;
+    if let Some(reader) = self.reader.take() {
+      reader.abort();
+    }
+  }
+}
+
+fn apply_lights(frame: &RemoteFrame, tracker: &mut SeqTracker, state: &SliderState) {
+  match frame.kind {
+    RemoteKind::Hello => tracker.restart(),
+    RemoteKind::Lights => {
+      if tracker.accept(frame.seq) {
+        let mut lights_handle = state.lights.lock();
+        frame.apply_lights(&mut lights_handle);
+      }
+    }
+    RemoteKind::Input => {}
+  }
+}
+
+#[async_trait]
+impl AsyncJob for NetworkOutputJob {
+  async fn setup(&mut self) -> bool {
+    self.connect().await;
+
+    true
+  }
+
+  async fn tick(&mut self) -> bool {
+    self.timer.tick().await;
+
+    if self.link.is_none() {
+      self.connect().await;
+    }
+
+    // Input is sent every tick even if unchanged so a lost frame is replaced
+    // by the next one
+    let seq = self.next_seq();
+    let data = {
+      let input_handle = self.state.input.lock();
+      RemoteFrame::input(seq, &input_handle).serialize()
+    };
+
+    match self.link.as_mut() {
+      Some(Link::Udp(socket)) => {
+        socket.send(&data).await.ok();
+        true
+      }
+      Some(Link::Tcp(write_half)) => {
+        if let Err(e) = write_half.write_all(&data).await {
+          error!("Network output connection error: {}", e);
+          self.disconnect();
+        }
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+impl Drop for NetworkOutputJob {
+  fn drop(&mut self) {
+    // Best effort release of all keys on the remote
+    let data = RemoteFrame::input(self.seq, &SliderInput::new()).serialize();
+    match self.link.as_ref() {
+      Some(Link::Udp(socket)) => {
+        socket.try_send(&data).ok();
+      }
+      Some(Link::Tcp(write_half)) => {
+        write_half.try_write(&data).ok();
+      }
+      None => {}
+    }
+    self.disconnect();
+  }
+}
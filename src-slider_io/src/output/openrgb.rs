@@ -0,0 +1,261 @@
+use async_trait::async_trait;
+use log::{error, info};
+use std::io;
+use tokio::{
+  io::{AsyncReadExt, AsyncWriteExt},
+  net::{lookup_host, TcpStream},
+  time::{interval, timeout, Duration, Instant, Interval},
+};
+
+use crate::{
+  shared::{
+    openrgb::{
+      serialize_colors, serialize_packet, OpenRgbController, OpenRgbHeader, DEFAULT_PORT,
+      HEADER_LEN, REQUEST_CONTROLLER_COUNT, REQUEST_CONTROLLER_DATA, SET_CLIENT_NAME,
+      SET_CUSTOM_MODE,
+    },
+    utils::{stretch_colors, ErrorLog},
+    worker::AsyncJob,
+  },
+  state::SliderState,
+};
+
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(5000);
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(2000);
+/// Colours are sent again this often even if they did not change, in case
+/// another program changed the device.
+const KEEPALIVE: Duration = Duration::from_millis(1000);
+/// Longest packet accepted from the server. Controller data with thousands of
+/// LEDs stays well below this, anything longer is a broken or hostile server.
+const MAX_PACKET_LEN: usize = 1 << 20;
+
+/// Reads packets until the response to `id`, skipping notifications such as
+/// device list updates.
+async fn read_response(stream: &mut TcpStream, id: u32) -> io::Result<Vec<u8>> {
+  loop {
+    let mut header = [0; HEADER_LEN];
+    stream.read_exact(&mut header).await?;
+    let header = OpenRgbHeader::parse(&header)
+      .ok_or(io::Error::new(io::ErrorKind::InvalidData, "bad packet"))?;
+    if header.len > MAX_PACKET_LEN {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "packet too long",
+      ));
+    }
+    let mut data = vec![0; header.len];
+    stream.read_exact(&mut data).await?;
+    if header.id == id {
+      return Ok(data);
+    }
+  }
+}
+
+async fn request(stream: &mut TcpStream, device: u32, id: u32) -> io::Result<Vec<u8>> {
+  stream.write_all(&serialize_packet(device, id, &[])).await?;
+  match timeout(RESPONSE_TIMEOUT, read_response(stream, id)).await {
+    Ok(res) => res,
+    Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "no response")),
+  }
+}
+
+/// The LEDs of the OpenRGB server that follow the slider.
+struct Target {
+  device: u32,
+  zone: Option<u32>,
+  leds: usize,
+}
+
+/// Mirrors the ground lights to a device or zone of an OpenRGB SDK server,
+/// stretched over its LEDs. Colours are sent when they change. The lights are
+/// polled and compared with the last colours sent rather than following
+/// `SliderLights.dirty`, as the controller's own light writer clears that flag
+/// and the two would miss each other's updates.
+pub struct OpenRgbJob {
+  state: SliderState,
+  addr: String,
+  device: String,
+  zone: String,
+  timer: Interval,
+  stream: Option<TcpStream>,
+  target: Option<Target>,
+  last_connect: Option<Instant>,
+  last_sent: Option<(Instant, [u8; 3 * 31])>,
+  last_error: Option<String>,
+  errors: ErrorLog,
+}
+
+impl OpenRgbJob {
+  pub fn new(
+    state: &SliderState,
+    addr: &String,
+    device: &String,
+    zone: &String,
+    errors: &ErrorLog,
+  ) -> Self {
+    Self {
+      state: state.clone(),
+      addr: addr.clone(),
+      device: device.to_lowercase(),
+      zone: zone.to_lowercase(),
+      timer: interval(Duration::from_micros(33333)),
+      stream: None,
+      target: None,
+      last_connect: None,
+      last_sent: None,
+      last_error: None,
+      errors: errors.clone(),
+    }
+  }
+
+  /// Reports an error once until the connection works again.
+  fn report(&mut self, e: String) {
+    if self.last_error.as_ref() != Some(&e) {
+      self.errors.push(e.clone());
+      self.last_error = Some(e);
+    }
+  }
+
+  async fn connect(&mut self) -> Result<(), String> {
+    let target = match self.addr.contains(':') {
+      true => self.addr.clone(),
+      false => format!("{}:{}", self.addr, DEFAULT_PORT),
+    };
+    let addr = lookup_host(target.as_str())
+      .await
+      .ok()
+      .and_then(|mut x| x.next())
+      .ok_or(format!("OpenRGB output cannot resolve {}", target))?;
+    let mut stream = TcpStream::connect(addr)
+      .await
+      .map_err(|e| format!("OpenRGB output cannot connect to {}: {}", addr, e))?;
+    stream.set_nodelay(true).ok();
+
+    let io_error = |e: io::Error| format!("OpenRGB output lost connection to {}: {}", addr, e);
+    stream
+      .write_all(&serialize_packet(0, SET_CLIENT_NAME, b"slidershim\0"))
+      .await
+      .map_err(io_error)?;
+
+    let count = request(&mut stream, 0, REQUEST_CONTROLLER_COUNT)
+      .await
+      .map_err(io_error)?;
+    let count = u32::from_le_bytes(count.get(0..4).ok_or("bad count")?.try_into().unwrap());
+    let mut controllers = vec![];
+    for device in 0..count {
+      let data = request(&mut stream, device, REQUEST_CONTROLLER_DATA)
+        .await
+        .map_err(io_error)?;
+      if let Some(controller) = OpenRgbController::parse(&data) {
+        controllers.push((device, controller));
+      }
+    }
+
+    let names: Vec<String> = controllers
+      .iter()
+      .map(|(_, x)| {
+        let zones: Vec<&str> = x.zones.iter().map(|z| z.name.as_str()).collect();
+        format!("{} ({})", x.name, zones.join(", "))
+      })
+      .collect();
+    info!("OpenRGB devices: {}", names.join("; "));
+
+    let (device, controller) = controllers
+      .iter()
+      .find(|(_, x)| x.name.to_lowercase().contains(&self.device))
+      .ok_or(format!(
+        "OpenRGB output found no device matching \"{}\", available: {}",
+        self.device,
+        names.join("; ")
+      ))?;
+    let target = match self.zone.as_str() {
+      "" => Target {
+        device: *device,
+        zone: None,
+        leds: controller.leds,
+      },
+      _ => {
+        let (zone_idx, zone) = controller
+          .zones
+          .iter()
+          .enumerate()
+          .find(|(_, x)| x.name.to_lowercase().contains(&self.zone))
+          .ok_or(format!(
+            "OpenRGB output found no zone matching \"{}\" on {}",
+            self.zone, controller.name
+          ))?;
+        Target {
+          device: *device,
+          zone: Some(zone_idx as u32),
+          leds: zone.leds,
+        }
+      }
+    };
+
+    // Direct colours only show in the custom mode
+    stream
+      .write_all(&serialize_packet(target.device, SET_CUSTOM_MODE, &[]))
+      .await
+      .map_err(io_error)?;
+
+    info!(
+      "OpenRGB output connected to {} on {}, {} leds",
+      controller.name, addr, target.leds
+    );
+    self.stream = Some(stream);
+    self.target = Some(target);
+    self.last_sent = None;
+    self.last_error = None;
+
+    Ok(())
+  }
+}
+
+#[async_trait]
+impl AsyncJob for OpenRgbJob {
+  async fn setup(&mut self) -> bool {
+    true
+  }
+
+  async fn tick(&mut self) -> bool {
+    self.timer.tick().await;
+
+    if self.stream.is_none() {
+      if let Some(last_connect) = self.last_connect {
+        if last_connect.elapsed() < RECONNECT_INTERVAL {
+          return true;
+        }
+      }
+      self.last_connect = Some(Instant::now());
+
+      if let Err(e) = self.connect().await {
+        self.report(e);
+        return true;
+      }
+    }
+
+    let ground = self.state.lights.lock().output().ground;
+    if let Some((last_time, last_ground)) = self.last_sent.as_ref() {
+      if *last_ground == ground && last_time.elapsed() < KEEPALIVE {
+        return true;
+      }
+    }
+
+    let target = self.target.as_ref().unwrap();
+    let packet = serialize_colors(
+      target.device,
+      target.zone,
+      &stretch_colors(&ground, target.leds),
+    );
+    let stream = self.stream.as_mut().unwrap();
+    if let Err(e) = stream.write_all(&packet).await {
+      error!("OpenRGB output disconnected: {}", e);
+      self.stream = None;
+      self.target = None;
+      return true;
+    }
+    self.last_sent = Some((Instant::now(), ground));
+
+    true
+  }
+}
@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use log::info;
+use tokio::{
+  net::UdpSocket,
+  time::{interval, Duration, Interval},
+};
+
+use crate::{
+  shared::{
+    net,
+    osc::{serialize_bundle, OscArg, OscMessage},
+    utils::ErrorLog,
+    worker::AsyncJob,
+  },
+  state::SliderState,
+};
+
+use super::config::PollingRate;
+
+/// Port used when the address is given without one.
+const DEFAULT_PORT: u16 = 9000;
+
+/// Sends slider state as OSC over UDP every tick:
+/// - `/slider/ground` blob of 32 pad pressures
+/// - `/slider/air` and `/slider/extra` with one int per string or button
+/// - `/slider/ground/<n>`, `/slider/air/<n>` and `/slider/extra/<n>` with 1 or 0
+///   whenever that input is pressed or released
+pub struct OscJob {
+  state: SliderState,
+  addr: String,
+  sensitivity: u8,
+  timer: Interval,
+  socket: Option<UdpSocket>,
+  last_flat: Vec<bool>,
+  errors: ErrorLog,
+}
+
+impl OscJob {
+  pub fn new(
+    state: &SliderState,
+    addr: &String,
+    polling: &PollingRate,
+    sensitivity: &u8,
+    errors: &ErrorLog,
+  ) -> Self {
+    Self {
+      state: state.clone(),
+      addr: addr.clone(),
+      sensitivity: *sensitivity,
+      timer: interval(Duration::from_micros(polling.to_t_u64())),
+      socket: None,
+      last_flat: vec![false; 41],
+      errors: errors.clone(),
+    }
+  }
+}
+
+/// Address of the edge event for an index of the flat input.
+fn edge_addr(i: usize) -> String {
+  match i {
+    0..=31 => format!("/slider/ground/{}", i),
+    32..=37 => format!("/slider/air/{}", i - 32),
+    _ => format!("/slider/extra/{}", i - 38),
+  }
+}
+
+#[async_trait]
+impl AsyncJob for OscJob {
+  async fn setup(&mut self) -> bool {
+    match net::connect_udp("OSC output", &self.addr, DEFAULT_PORT).await {
+      Ok(socket) => {
+        info!("OSC output sending to {}", self.addr);
+        self.socket = Some(socket);
+        true
+      }
+      Err(e) => {
+        self.errors.push(e);
+        false
+      }
+    }
+  }
+
+  async fn tick(&mut self) -> bool {
+    self.timer.tick().await;
+
+    let (ground, air, extra, flat) = {
+      let input_handle = self.state.input.lock();
+      (
+        input_handle.ground.to_vec(),
+        input_handle.air,
+        input_handle.extra,
+        input_handle.to_flat(&self.sensitivity),
+      )
+    };
+
+    let ints = |x: &[u8]| x.iter().map(|x| OscArg::Int(*x as i32)).collect();
+    let mut messages = vec![
+      OscMessage::new("/slider/ground", vec![OscArg::Blob(ground)]),
+      OscMessage::new("/slider/air", ints(&air)),
+      OscMessage::new("/slider/extra", ints(&extra)),
+    ];
+
+    for (i, (pressed, last)) in flat.iter().zip(self.last_flat.iter()).enumerate() {
+      if pressed != last {
+        messages.push(OscMessage::new(
+          &edge_addr(i),
+          vec![OscArg::Int(*pressed as i32)],
+        ));
+      }
+    }
+    self.last_flat = flat;
+
+    if let Some(socket) = self.socket.as_ref() {
+      socket.send(&serialize_bundle(&messages)).await.ok();
+    }
+
+    true
+  }
+}
+
+impl Drop for OscJob {
+  fn drop(&mut self) {
+    // Best effort release of everything on the receiving end
+    let mut messages = vec![
+      OscMessage::new("/slider/ground", vec![OscArg::Blob(vec![0; 32])]),
+      OscMessage::new("/slider/air", vec![OscArg::Int(0); 6]),
+      OscMessage::new("/slider/extra", vec![OscArg::Int(0); 3]),
+    ];
+    for (i, last) in self.last_flat.iter().enumerate() {
+      if *last {
+        messages.push(OscMessage::new(&edge_addr(i), vec![OscArg::Int(0)]));
+      }
+    }
+
+    if let Some(socket) = self.socket.as_ref() {
+      socket.try_send(&serialize_bundle(&messages)).ok();
+    }
+  }
+}
@@ -3,10 +3,14 @@ use log::error;
 use std::time::Duration;
 use tokio::time::{interval, Interval};
 
-use crate::{shared::worker::AsyncJob, state::SliderState};
+use crate::{
+  shared::{utils::ErrorLog, worker::AsyncJob},
+  state::SliderState,
+};
 
 use super::{
   config::OutputMode, gamepad::GamepadOutput, hori::HoriOutput, keyboard::KeyboardOutput,
+  midi::MidiOutput, mouse::MouseOutput,
 };
 
 pub trait OutputHandler: Send {
@@ -20,16 +24,18 @@ pub struct OutputJob {
   sensitivity: u8,
   handler: Option<Box<dyn OutputHandler>>,
   timer: Interval,
+  errors: ErrorLog,
 }
 
 impl OutputJob {
-  pub fn new(state: &SliderState, mode: &OutputMode) -> Self {
+  pub fn new(state: &SliderState, mode: &OutputMode, errors: &ErrorLog) -> Self {
     Self {
       state: state.clone(),
       mode: mode.clone(),
       sensitivity: 0,
       handler: None,
       timer: interval(Duration::MAX),
+      errors: errors.clone(),
     }
   }
 }
@@ -84,6 +90,41 @@ impl AsyncJob for OutputJob {
           None => false,
         }
       }
+      OutputMode::Mouse {
+        relative,
+        speed,
+        smoothing,
+        polling,
+        sensitivity,
+      } => {
+        self.sensitivity = sensitivity;
+        self.handler = Some(Box::new(MouseOutput::new(relative, speed, smoothing)));
+        self.timer = interval(Duration::from_micros(polling.to_t_u64()));
+
+        true
+      }
+      OutputMode::Midi {
+        ref port,
+        channel,
+        ref layout,
+        polling,
+        sensitivity,
+      } => {
+        self.sensitivity = sensitivity;
+        let handler = MidiOutput::new(&self.state, port, channel, layout);
+        self.timer = interval(Duration::from_micros(polling.to_t_u64()));
+
+        match handler {
+          Ok(handler) => {
+            self.handler = Some(Box::new(handler));
+            true
+          }
+          Err(e) => {
+            self.errors.push(e);
+            false
+          }
+        }
+      }
       _ => {
         error!("Not implemented");
         false
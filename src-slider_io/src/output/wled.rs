@@ -0,0 +1,111 @@
+use async_trait::async_trait;
+use log::info;
+use tokio::{
+  net::UdpSocket,
+  time::{interval, Duration, Interval},
+};
+
+use crate::{
+  shared::{
+    net,
+    utils::ErrorLog,
+    wled::{lights_to_pixels, PixelLayout, WledProtocol},
+    worker::AsyncJob,
+  },
+  state::SliderState,
+};
+
+/// Streams the slider lights to an LED strip over DDP or WLED realtime UDP.
+/// Frames are sent at a steady rate, as WLED returns to its own effects when
+/// packets stop.
+pub struct WledJob {
+  state: SliderState,
+  protocol: WledProtocol,
+  addr: String,
+  pixels: usize,
+  air_pixels: usize,
+  layout: PixelLayout,
+  timer: Interval,
+  socket: Option<UdpSocket>,
+  seq: u8,
+  errors: ErrorLog,
+}
+
+impl WledJob {
+  pub fn new(
+    state: &SliderState,
+    protocol: &WledProtocol,
+    addr: &String,
+    pixels: &usize,
+    air_pixels: &usize,
+    layout: &PixelLayout,
+    rate: &u8,
+    errors: &ErrorLog,
+  ) -> Self {
+    Self {
+      state: state.clone(),
+      protocol: *protocol,
+      addr: addr.clone(),
+      pixels: *pixels,
+      air_pixels: *air_pixels,
+      layout: *layout,
+      timer: interval(Duration::from_secs(1) / *rate as u32),
+      socket: None,
+      seq: 0,
+      errors: errors.clone(),
+    }
+  }
+}
+
+#[async_trait]
+impl AsyncJob for WledJob {
+  async fn setup(&mut self) -> bool {
+    if self.addr.trim().is_empty() {
+      self
+        .errors
+        .push("WLED output needs the address of the strip".to_string());
+      return false;
+    }
+
+    match net::connect_udp("WLED output", &self.addr, self.protocol.port()).await {
+      Ok(socket) => {
+        info!(
+          "WLED output sending {:?}, {} + 2x{} pixels to {}",
+          self.protocol, self.pixels, self.air_pixels, self.addr
+        );
+        self.socket = Some(socket);
+        true
+      }
+      Err(e) => {
+        self.errors.push(e);
+        false
+      }
+    }
+  }
+
+  async fn tick(&mut self) -> bool {
+    self.timer.tick().await;
+
+    let pixels = {
+      let lights_handle = self.state.lights.lock();
+      lights_to_pixels(
+        &lights_handle.output(),
+        self.pixels,
+        self.air_pixels,
+        self.layout,
+      )
+    };
+
+    // DDP sequence numbers are 1-15, 0 means unused
+    self.seq = self.seq % 15 + 1;
+    let packets = self.protocol.serialize(self.seq, &pixels);
+
+    if let Some(socket) = self.socket.as_ref() {
+      for packet in packets {
+        socket.send(&packet).await.ok();
+      }
+    }
+
+    true
+  }
+}
@@ -0,0 +1,192 @@
+use serde_json::Value;
+
+use crate::state::SliderLights;
+
+// DMX512 over ethernet, one universe of up to 512 channels per packet. Lights
+// take 37 RGB pixels from the start channel: 31 ground pixels from the left,
+// then the left air pixels and the right air pixels from the bottom.
+//
+// sACN (ANSI E1.31) packets are a root layer, a framing layer and a DMP layer
+// with big endian lengths, universes 1-63999 are multicast to
+// 239.255.<universe high byte>.<universe low byte>. Art-Net ArtDmx packets are
+// an 18 byte header followed by the channels, universes 0-32767 are usually
+// broadcast.
+
+/// Number of channels used by the lights.
+pub const LIGHTS_CHANNELS: usize = 3 * (31 + 3 + 3);
+/// Last start channel that still fits the lights in a universe.
+pub const MAX_START_CHANNEL: usize = 512 - LIGHTS_CHANNELS + 1;
+
+pub const SACN_PORT: u16 = 5568;
+pub const ARTNET_PORT: u16 = 6454;
+
+const SACN_ID: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const SACN_DATA_START: usize = 126;
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const ARTNET_DATA_START: usize = 18;
+
+/// Source name sent in sACN packets.
+const SOURCE_NAME: &str = "slidershim";
+/// sACN component identifier, a UUID that should stay the same for a sender.
+const CID: [u8; 16] = [
+  0x73, 0x6c, 0x69, 0x64, 0x65, 0x72, 0x73, 0x68, 0x69, 0x6d, 0x40, 0x00, 0x80, 0x00, 0x00, 0x01,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmxProtocol {
+  Sacn,
+  ArtNet,
+}
+
+impl DmxProtocol {
+  pub fn from_str(s: &str) -> Option<Self> {
+    match s {
+      "sacn" => Some(DmxProtocol::Sacn),
+      "artnet" => Some(DmxProtocol::ArtNet),
+      _ => None,
+    }
+  }
+
+  /// Reads a universe number, checking that the protocol supports it.
+  pub fn parse_universe(&self, v: &Value) -> Option<u16> {
+    let universe = u16::try_from(v.as_i64()?).ok()?;
+    match (self.min_universe()..=self.max_universe()).contains(&universe) {
+      true => Some(universe),
+      false => None,
+    }
+  }
+
+  pub fn port(&self) -> u16 {
+    match self {
+      DmxProtocol::Sacn => SACN_PORT,
+      DmxProtocol::ArtNet => ARTNET_PORT,
+    }
+  }
+
+  pub fn max_universe(&self) -> u16 {
+    match self {
+      DmxProtocol::Sacn => 63999,
+      DmxProtocol::ArtNet => 32767,
+    }
+  }
+
+  pub fn min_universe(&self) -> u16 {
+    match self {
+      DmxProtocol::Sacn => 1,
+      DmxProtocol::ArtNet => 0,
+    }
+  }
+
+  /// Serializes one packet carrying all 512 channels of a universe.
+  pub fn serialize(&self, universe: u16, seq: u8, channels: &[u8; 512]) -> Vec<u8> {
+    match self {
+      DmxProtocol::Sacn => {
+        let len = SACN_DATA_START + 512;
+        let flags_len = |offset: usize| (0x7000 | (len - offset) as u16).to_be_bytes();
+
+        let mut buf = Vec::with_capacity(len);
+        // Root layer
+        buf.extend(0x0010u16.to_be_bytes());
+        buf.extend(0x0000u16.to_be_bytes());
+        buf.extend(SACN_ID);
+        buf.extend(flags_len(16));
+        buf.extend(0x00000004u32.to_be_bytes());
+        buf.extend(CID);
+        // Framing layer
+        buf.extend(flags_len(38));
+        buf.extend(0x00000002u32.to_be_bytes());
+        let mut name = [0; 64];
+        name[..SOURCE_NAME.len()].copy_from_slice(SOURCE_NAME.as_bytes());
+        buf.extend(name);
+        buf.push(100); // priority
+        buf.extend(0u16.to_be_bytes()); // sync address
+        buf.push(seq);
+        buf.push(0); // options
+        buf.extend(universe.to_be_bytes());
+        // DMP layer
+        buf.extend(flags_len(115));
+        buf.push(0x02);
+        buf.push(0xa1);
+        buf.extend(0u16.to_be_bytes()); // first property address
+        buf.extend(1u16.to_be_bytes()); // address increment
+        buf.extend(513u16.to_be_bytes());
+        buf.push(0); // start code
+        buf.extend(channels);
+        buf
+      }
+      DmxProtocol::ArtNet => {
+        let mut buf = Vec::with_capacity(ARTNET_DATA_START + 512);
+        buf.extend(ARTNET_ID);
+        buf.extend(0x5000u16.to_le_bytes()); // OpDmx
+        buf.extend(14u16.to_be_bytes()); // protocol version
+        buf.push(seq);
+        buf.push(0); // physical port
+        buf.extend((universe & 0x7fff).to_le_bytes());
+        buf.extend(512u16.to_be_bytes());
+        buf.extend(channels);
+        buf
+      }
+    }
+  }
+
+  /// Reads the universe and channels of a DMX packet. Other packets, such as
+  /// Art-Net polls or sACN discovery, are ignored.
+  pub fn parse<'a>(&self, buf: &'a [u8]) -> Option<(u16, &'a [u8])> {
+    match self {
+      DmxProtocol::Sacn => {
+        if buf.get(4..16)? != SACN_ID
+          || buf.get(18..22)? != [0, 0, 0, 4]
+          || buf.get(40..44)? != [0, 0, 0, 2]
+          || *buf.get(117)? != 0x02
+          || *buf.get(125)? != 0
+        {
+          return None;
+        }
+        // Stream terminated
+        if *buf.get(112)? & 0x40 != 0 {
+          return None;
+        }
+        let universe = u16::from_be_bytes(buf.get(113..115)?.try_into().ok()?);
+        let count = u16::from_be_bytes(buf.get(123..125)?.try_into().ok()?) as usize;
+        let data = buf.get(SACN_DATA_START..SACN_DATA_START + count.checked_sub(1)?)?;
+        Some((universe, data))
+      }
+      DmxProtocol::ArtNet => {
+        if buf.get(0..8)? != ARTNET_ID || buf.get(8..10)? != [0x00, 0x50] {
+          return None;
+        }
+        let header = buf.get(14..ARTNET_DATA_START)?;
+        let universe = u16::from_le_bytes([header[0], header[1]]) & 0x7fff;
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let data = buf.get(ARTNET_DATA_START..ARTNET_DATA_START + len)?;
+        Some((universe, data))
+      }
+    }
+  }
+}
+
+/// Writes lights into a universe from `start`, the first channel counting
+/// from 1.
+pub fn lights_to_channels(lights: &SliderLights, start: usize, channels: &mut [u8; 512]) {
+  let data = lights
+    .ground
+    .iter()
+    .chain(lights.air_left.iter())
+    .chain(lights.air_right.iter());
+  for (channel, x) in channels[start - 1..].iter_mut().zip(data) {
+    *channel = *x;
+  }
+}
+
+/// Reads lights from a universe from `start`, the first channel counting from
+/// 1. Channels missing from a short universe are treated as 0.
+pub fn channels_to_lights(channels: &[u8], start: usize, lights: &mut SliderLights) {
+  let data = lights
+    .ground
+    .iter_mut()
+    .chain(lights.air_left.iter_mut())
+    .chain(lights.air_right.iter_mut());
+  for (i, x) in data.enumerate() {
+    *x = channels.get(start - 1 + i).copied().unwrap_or(0);
+  }
+}
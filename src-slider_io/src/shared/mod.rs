@@ -1,5 +1,11 @@
+pub mod dmx;
 pub mod hori;
+pub mod net;
+pub mod openrgb;
+pub mod osc;
+pub mod remote;
 pub mod serial;
 pub mod utils;
 pub mod voltex;
+pub mod wled;
 pub mod worker;
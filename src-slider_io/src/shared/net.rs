@@ -0,0 +1,132 @@
+use log::error;
+use socket2::{Domain, Socket, Type};
+use std::{
+  io,
+  net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, UdpSocket},
+};
+use tokio::net::{lookup_host, UdpSocket as TokioUdpSocket};
+
+/// Parses a comma or space separated list of listen addresses, e.g.
+/// `0.0.0.0, ::` or `127.0.0.1`. IPv6 addresses may be wrapped in brackets.
+/// Falls back to every IPv4 interface if nothing valid is given.
+pub fn parse_bind_addrs(s: &str) -> Vec<IpAddr> {
+  let mut addrs = vec![];
+  for part in s.split(|c: char| c == ',' || c.is_whitespace()) {
+    let part = part.trim_start_matches('[').trim_end_matches(']');
+    if part.is_empty() {
+      continue;
+    }
+
+    match part.parse::<IpAddr>() {
+      Ok(addr) => {
+        if !addrs.contains(&addr) {
+          addrs.push(addr);
+        }
+      }
+      Err(_) => error!("Invalid listen address {}", part),
+    }
+  }
+
+  if addrs.is_empty() {
+    addrs.push(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+  }
+
+  addrs
+}
+
+/// Opens a listening socket on `addr`. IPv6 sockets are always v6-only so that
+/// listing both `0.0.0.0` and `::` gives dual-stack on every platform.
+pub fn bind_tcp(addr: &SocketAddr) -> io::Result<TcpListener> {
+  let socket = Socket::new(Domain::for_address(*addr), Type::STREAM, None)?;
+  if addr.is_ipv6() {
+    socket.set_only_v6(true)?;
+  }
+  // Matches std, which only sets this outside of windows where it would allow
+  // two servers to share a port
+  #[cfg(not(windows))]
+  socket.set_reuse_address(true)?;
+  socket.bind(&(*addr).into())?;
+  socket.listen(1024)?;
+  socket.set_nonblocking(true)?;
+
+  Ok(socket.into())
+}
+
+/// Opens a UDP socket on `addr`, v6-only like `bind_tcp`.
+pub fn bind_udp(addr: &SocketAddr) -> io::Result<UdpSocket> {
+  let socket = Socket::new(Domain::for_address(*addr), Type::DGRAM, None)?;
+  if addr.is_ipv6() {
+    socket.set_only_v6(true)?;
+  }
+  socket.bind(&(*addr).into())?;
+  socket.set_nonblocking(true)?;
+
+  Ok(socket.into())
+}
+
+/// Adds `default_port` to an address given without one, e.g. `192.168.1.2`,
+/// `::1` or `wled.local`.
+pub fn with_default_port(addr: &str, default_port: u16) -> String {
+  let addr = addr.trim();
+  match addr.parse::<IpAddr>() {
+    Ok(ip) => SocketAddr::new(ip, default_port).to_string(),
+    Err(_) if addr.contains(':') => addr.to_string(),
+    Err(_) => format!("{}:{}", addr, default_port),
+  }
+}
+
+/// Resolves `addr` and opens a UDP socket connected to it, for outputs that
+/// stream to a single receiver. Broadcast is always allowed so that broadcast
+/// addresses of any subnet work. Errors are described with `name`, e.g.
+/// "DMX output". Send errors afterwards are usually nothing listening yet, so
+/// outputs ignore them and keep sending.
+pub async fn connect_udp(
+  name: &str,
+  addr: &str,
+  default_port: u16,
+) -> Result<TokioUdpSocket, String> {
+  let target = with_default_port(addr, default_port);
+  let addr = lookup_host(target.as_str())
+    .await
+    .ok()
+    .and_then(|mut x| x.next())
+    .ok_or(format!("{} cannot resolve {}", name, target))?;
+
+  let local_addr: SocketAddr = match addr.is_ipv6() {
+    false => (Ipv4Addr::UNSPECIFIED, 0).into(),
+    true => (Ipv6Addr::UNSPECIFIED, 0).into(),
+  };
+  let socket = TokioUdpSocket::bind(local_addr)
+    .await
+    .map_err(|e| format!("{} socket error: {}", name, e))?;
+  if addr.is_ipv4() {
+    socket.set_broadcast(true).ok();
+  }
+  socket
+    .connect(addr)
+    .await
+    .map_err(|e| format!("{} cannot reach {}: {}", name, addr, e))?;
+
+  Ok(socket)
+}
+
+/// Describes a failure to listen on `addr` in terms the user can act on.
+pub fn describe_bind_error(name: &str, addr: &SocketAddr, e: &io::Error) -> String {
+  match e.kind() {
+    io::ErrorKind::AddrInUse => format!(
+      "{} cannot listen on {}: port {} is already in use by another program",
+      name,
+      addr,
+      addr.port()
+    ),
+    io::ErrorKind::AddrNotAvailable => format!(
+      "{} cannot listen on {}: address does not belong to this computer",
+      name, addr
+    ),
+    io::ErrorKind::PermissionDenied => format!(
+      "{} cannot listen on {}: access denied, the port may be reserved by the system",
+      name, addr
+    ),
+    _ => format!("{} cannot listen on {}: {}", name, addr, e),
+  }
+}
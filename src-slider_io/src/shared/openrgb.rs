@@ -0,0 +1,150 @@
+// Client side of the OpenRGB SDK protocol, only what is needed to list
+// controllers and set their colours. Every packet is a 16 byte header of the
+// magic `ORGB`, then little endian u32 device index, packet id and data size,
+// followed by the data. Controller data is requested with protocol version 0,
+// which every server supports.
+
+pub const DEFAULT_PORT: u16 = 6742;
+pub const HEADER_LEN: usize = 16;
+
+const MAGIC: [u8; 4] = *b"ORGB";
+
+pub const REQUEST_CONTROLLER_COUNT: u32 = 0;
+pub const REQUEST_CONTROLLER_DATA: u32 = 1;
+pub const SET_CLIENT_NAME: u32 = 50;
+pub const UPDATE_LEDS: u32 = 1050;
+pub const UPDATE_ZONE_LEDS: u32 = 1051;
+pub const SET_CUSTOM_MODE: u32 = 1100;
+
+pub struct OpenRgbHeader {
+  pub device: u32,
+  pub id: u32,
+  pub len: usize,
+}
+
+impl OpenRgbHeader {
+  pub fn parse(buf: &[u8; HEADER_LEN]) -> Option<Self> {
+    if buf[0..4] != MAGIC {
+      return None;
+    }
+    let u32_at = |i: usize| u32::from_le_bytes(buf[i..i + 4].try_into().unwrap());
+    Some(Self {
+      device: u32_at(4),
+      id: u32_at(8),
+      len: u32_at(12) as usize,
+    })
+  }
+}
+
+pub fn serialize_packet(device: u32, id: u32, data: &[u8]) -> Vec<u8> {
+  let mut buf = Vec::with_capacity(HEADER_LEN + data.len());
+  buf.extend(MAGIC);
+  buf.extend(device.to_le_bytes());
+  buf.extend(id.to_le_bytes());
+  buf.extend((data.len() as u32).to_le_bytes());
+  buf.extend(data);
+  buf
+}
+
+/// Sets every LED of a controller, or of one zone if `zone` is given.
+pub fn serialize_colors(device: u32, zone: Option<u32>, colors: &[[u8; 3]]) -> Vec<u8> {
+  let mut data = vec![];
+  data.extend([0; 4]); // size, filled in below
+  if let Some(zone) = zone {
+    data.extend(zone.to_le_bytes());
+  }
+  data.extend((colors.len() as u16).to_le_bytes());
+  for color in colors {
+    data.extend(color);
+    data.push(0);
+  }
+  let len = data.len() as u32;
+  data[0..4].copy_from_slice(&len.to_le_bytes());
+
+  let id = match zone {
+    Some(_) => UPDATE_ZONE_LEDS,
+    None => UPDATE_LEDS,
+  };
+  serialize_packet(device, id, &data)
+}
+
+struct Reader<'a> {
+  buf: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+    let data = self.buf.get(self.pos..self.pos + len)?;
+    self.pos += len;
+    Some(data)
+  }
+
+  fn u16(&mut self) -> Option<u16> {
+    Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+  }
+
+  fn u32(&mut self) -> Option<u32> {
+    Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+  }
+
+  /// Strings are a u16 length including the terminating zero.
+  fn string(&mut self) -> Option<String> {
+    let len = self.u16()? as usize;
+    let data = self.take(len)?;
+    let data = data.strip_suffix(&[0]).unwrap_or(data);
+    Some(String::from_utf8_lossy(data).to_string())
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenRgbZone {
+  pub name: String,
+  pub leds: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenRgbController {
+  pub name: String,
+  pub zones: Vec<OpenRgbZone>,
+  pub leds: usize,
+}
+
+impl OpenRgbController {
+  /// Parses the protocol version 0 response to `REQUEST_CONTROLLER_DATA`.
+  pub fn parse(buf: &[u8]) -> Option<Self> {
+    let mut r = Reader { buf, pos: 0 };
+    r.u32()?; // data size
+    r.u32()?; // type
+    let name = r.string()?;
+    for _ in 0..4 {
+      r.string()?; // description, version, serial, location
+    }
+
+    let num_modes = r.u16()?;
+    r.u32()?; // active mode
+    for _ in 0..num_modes {
+      r.string()?;
+      // value, flags, speed min and max, colors min and max, speed, direction
+      // and color mode
+      r.take(4 * 9)?;
+      let num_colors = r.u16()? as usize;
+      r.take(4 * num_colors)?;
+    }
+
+    let num_zones = r.u16()?;
+    let mut zones = vec![];
+    for _ in 0..num_zones {
+      let name = r.string()?;
+      r.take(4 * 3)?; // type, leds min and max
+      let leds = r.u32()? as usize;
+      let matrix_len = r.u16()? as usize;
+      r.take(matrix_len)?;
+      zones.push(OpenRgbZone { name, leds });
+    }
+
+    let leds = r.u16()? as usize;
+
+    Some(Self { name, zones, leds })
+  }
+}
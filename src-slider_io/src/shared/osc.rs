@@ -0,0 +1,125 @@
+// Minimal Open Sound Control 1.0 encoding, only int and blob arguments are
+// needed. Every string and blob is padded with zeros to a multiple of 4 bytes
+// and numbers are big endian.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OscArg {
+  Int(i32),
+  Blob(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OscMessage {
+  pub addr: String,
+  pub args: Vec<OscArg>,
+}
+
+fn write_padded(buf: &mut Vec<u8>, data: &[u8]) {
+  buf.extend(data);
+  buf.resize(buf.len() + 4 - data.len() % 4, 0);
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+  // Strings always end with at least one zero
+  write_padded(buf, s.as_bytes());
+}
+
+fn read_str(buf: &[u8]) -> Option<(String, usize)> {
+  let end = buf.iter().position(|x| *x == 0)?;
+  let s = String::from_utf8(buf[..end].to_vec()).ok()?;
+  Some((s, (end / 4 + 1) * 4))
+}
+
+fn read_i32(buf: &[u8]) -> Option<i32> {
+  Some(i32::from_be_bytes(buf.get(0..4)?.try_into().ok()?))
+}
+
+impl OscMessage {
+  pub fn new(addr: &str, args: Vec<OscArg>) -> Self {
+    Self {
+      addr: addr.to_string(),
+      args,
+    }
+  }
+
+  pub fn serialize(&self) -> Vec<u8> {
+    let mut buf = vec![];
+    write_str(&mut buf, &self.addr);
+
+    let tags: String = std::iter::once(',')
+      .chain(self.args.iter().map(|arg| match arg {
+        OscArg::Int(_) => 'i',
+        OscArg::Blob(_) => 'b',
+      }))
+      .collect();
+    write_str(&mut buf, &tags);
+
+    for arg in self.args.iter() {
+      match arg {
+        OscArg::Int(x) => buf.extend(x.to_be_bytes()),
+        OscArg::Blob(data) => {
+          buf.extend((data.len() as i32).to_be_bytes());
+          buf.extend(data);
+          buf.resize((buf.len() + 3) / 4 * 4, 0);
+        }
+      }
+    }
+
+    buf
+  }
+
+  /// Parses a message or every message in a bundle.
+  pub fn parse_packet(buf: &[u8]) -> Option<Vec<Self>> {
+    if buf.starts_with(b"#bundle\0") {
+      let mut messages = vec![];
+      let mut i = 16;
+      while i < buf.len() {
+        let len = usize::try_from(read_i32(&buf[i..])?).ok()?;
+        let end = (i + 4).checked_add(len)?;
+        messages.extend(Self::parse_packet(buf.get(i + 4..end)?)?);
+        i = end;
+      }
+      return Some(messages);
+    }
+
+    let (addr, mut i) = read_str(buf)?;
+    let (tags, len) = read_str(buf.get(i..)?)?;
+    i += len;
+
+    let mut args = vec![];
+    for tag in tags.strip_prefix(',')?.chars() {
+      match tag {
+        'i' => {
+          args.push(OscArg::Int(read_i32(buf.get(i..)?)?));
+          i += 4;
+        }
+        'b' => {
+          // Negative or oversized lengths make the packet malformed
+          let len = usize::try_from(read_i32(buf.get(i..)?)?).ok()?;
+          let end = (i + 4).checked_add(len)?;
+          args.push(OscArg::Blob(buf.get(i + 4..end)?.to_vec()));
+          i = (end + 3) / 4 * 4;
+        }
+        _ => return None,
+      }
+    }
+
+    Some(vec![Self { addr, args }])
+  }
+}
+
+/// Packs messages into a bundle to be handled immediately, so that all of them
+/// fit in a single datagram.
+pub fn serialize_bundle(messages: &[OscMessage]) -> Vec<u8> {
+  let mut buf = vec![];
+  write_str(&mut buf, "#bundle");
+  buf.extend(1u64.to_be_bytes());
+
+  for message in messages {
+    let data = message.serialize();
+    buf.extend((data.len() as i32).to_be_bytes());
+    buf.extend(data);
+  }
+
+  buf
+}
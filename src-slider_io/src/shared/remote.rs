@@ -0,0 +1,204 @@
+use crate::state::{SliderInput, SliderLights};
+
+use super::utils::ShimError;
+
+// Frames used to link two slidershim instances over the network. Each frame is
+// the magic `SL`, a kind byte, a big endian u32 sequence number and a fixed
+// size payload that only depends on the kind, so the same framing works for
+// both UDP datagrams and a TCP byte stream.
+//
+// | kind | payload                                    | bytes |
+// |------|--------------------------------------------|-------|
+// | 0x01 | input: ground 32, air 6, extra 3           | 41    |
+// | 0x02 | lights: ground 93, air left 9, air right 9 | 111   |
+// | 0x03 | hello: sent when a sender (re)starts       | 0     |
+
+const MAGIC: [u8; 2] = *b"SL";
+const HEADER_LEN: usize = 7;
+
+/// Largest possible frame, useful for sizing receive buffers.
+pub const MAX_FRAME_LEN: usize = HEADER_LEN + 111;
+
+/// Port used when an address is given without one.
+pub const DEFAULT_PORT: u16 = 1607;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteTransport {
+  Udp,
+  Tcp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteKind {
+  Input,
+  Lights,
+  Hello,
+}
+
+impl RemoteKind {
+  fn from_u8(x: u8) -> Option<Self> {
+    match x {
+      0x01 => Some(RemoteKind::Input),
+      0x02 => Some(RemoteKind::Lights),
+      0x03 => Some(RemoteKind::Hello),
+      _ => None,
+    }
+  }
+
+  fn to_u8(&self) -> u8 {
+    match self {
+      RemoteKind::Input => 0x01,
+      RemoteKind::Lights => 0x02,
+      RemoteKind::Hello => 0x03,
+    }
+  }
+
+  fn payload_len(&self) -> usize {
+    match self {
+      RemoteKind::Input => 32 + 6 + 3,
+      RemoteKind::Lights => 93 + 9 + 9,
+      RemoteKind::Hello => 0,
+    }
+  }
+}
+
+pub struct RemoteFrame {
+  pub kind: RemoteKind,
+  pub seq: u32,
+  pub payload: Vec<u8>,
+}
+
+impl RemoteFrame {
+  pub fn input(seq: u32, input: &SliderInput) -> Self {
+    let mut payload = Vec::with_capacity(RemoteKind::Input.payload_len());
+    payload.extend(input.ground);
+    payload.extend(input.air);
+    payload.extend(input.extra);
+
+    Self {
+      kind: RemoteKind::Input,
+      seq,
+      payload,
+    }
+  }
+
+  pub fn lights(seq: u32, lights: &SliderLights) -> Self {
+    let mut payload = Vec::with_capacity(RemoteKind::Lights.payload_len());
+    payload.extend(lights.ground);
+    payload.extend(lights.air_left);
+    payload.extend(lights.air_right);
+
+    Self {
+      kind: RemoteKind::Lights,
+      seq,
+      payload,
+    }
+  }
+
+  pub fn hello(seq: u32) -> Self {
+    Self {
+      kind: RemoteKind::Hello,
+      seq,
+      payload: vec![],
+    }
+  }
+
+  pub fn serialize(&self) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_LEN + self.payload.len());
+    buf.extend(MAGIC);
+    buf.push(self.kind.to_u8());
+    buf.extend(self.seq.to_be_bytes());
+    buf.extend(&self.payload);
+
+    buf
+  }
+
+  /// Parses a frame from the start of `buf`, returning it together with the
+  /// number of bytes used. Returns `Ok(None)` if `buf` does not hold a full
+  /// frame yet and an error if it does not start with a valid header.
+  pub fn parse(buf: &[u8]) -> Result<Option<(Self, usize)>, ShimError> {
+    if buf.len() < HEADER_LEN {
+      return Ok(None);
+    }
+    if buf[0..2] != MAGIC {
+      return Err(ShimError);
+    }
+    let kind = RemoteKind::from_u8(buf[2]).ok_or(ShimError)?;
+    let len = HEADER_LEN + kind.payload_len();
+    if buf.len() < len {
+      return Ok(None);
+    }
+
+    let seq = u32::from_be_bytes([buf[3], buf[4], buf[5], buf[6]]);
+    Ok(Some((
+      Self {
+        kind,
+        seq,
+        payload: buf[HEADER_LEN..len].to_vec(),
+      },
+      len,
+    )))
+  }
+
+  pub fn apply_input(&self, input: &mut SliderInput) {
+    if self.kind == RemoteKind::Input {
+      input.ground.copy_from_slice(&self.payload[0..32]);
+      input.air.copy_from_slice(&self.payload[32..38]);
+      input.extra.copy_from_slice(&self.payload[38..41]);
+    }
+  }
+
+  pub fn apply_lights(&self, lights: &mut SliderLights) {
+    if self.kind == RemoteKind::Lights {
+      lights.ground.copy_from_slice(&self.payload[0..93]);
+      lights.air_left.copy_from_slice(&self.payload[93..102]);
+      lights.air_right.copy_from_slice(&self.payload[102..111]);
+      lights.dirty = true;
+    }
+  }
+}
+
+/// Tracks sequence numbers of received frames so that late or duplicated
+/// frames can be dropped and gaps counted as lost.
+pub struct SeqTracker {
+  last: Option<u32>,
+  pub received: u64,
+  pub lost: u64,
+  pub stale: u64,
+}
+
+impl SeqTracker {
+  pub fn new() -> Self {
+    Self {
+      last: None,
+      received: 0,
+      lost: 0,
+      stale: 0,
+    }
+  }
+
+  /// Forgets the last sequence number, used when the sender restarts.
+  pub fn restart(&mut self) {
+    self.last = None;
+  }
+
+  /// Returns false if the frame is not newer than one already accepted and
+  /// should be ignored.
+  pub fn accept(&mut self, seq: u32) -> bool {
+    if let Some(last) = self.last {
+      let delta = seq.wrapping_sub(last) as i32;
+      // A big jump backwards is a sender that restarted without saying hello
+      if delta <= 0 && delta > -1024 {
+        self.stale += 1;
+        return false;
+      }
+      if delta > 1 {
+        self.lost += (delta - 1) as u64;
+      }
+    }
+
+    self.last = Some(seq);
+    self.received += 1;
+    true
+  }
+}
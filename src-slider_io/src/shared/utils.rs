@@ -1,4 +1,6 @@
 use atomic_float::AtomicF64;
+use log::error;
+use parking_lot::Mutex;
 use std::{
   error::Error,
   fmt,
@@ -40,6 +42,32 @@ impl Error for ShimError {
   }
 }
 
+/// Errors raised by running jobs that should be shown to the user until the
+/// workers are rebuilt.
+#[derive(Clone)]
+pub struct ErrorLog {
+  errors: Arc<Mutex<Vec<String>>>,
+}
+
+impl ErrorLog {
+  pub fn new() -> Self {
+    Self {
+      errors: Arc::new(Mutex::new(vec![])),
+    }
+  }
+
+  pub fn push(&self, e: String) {
+    error!("{}", e);
+    let mut errors_handle = self.errors.lock();
+    errors_handle.push(e);
+  }
+
+  pub fn snapshot(&self) -> Vec<String> {
+    let errors_handle = self.errors.lock();
+    errors_handle.clone()
+  }
+}
+
 pub struct LoopTimer {
   cap: usize,
   cur: usize,
@@ -91,3 +119,25 @@ impl LoopTimer {
     Arc::clone(&self.freq)
   }
 }
+
+/// Stretches RGB pixels over `len` pixels, blending neighbouring colours when
+/// a pixel falls between two source pixels.
+pub fn stretch_colors(colors: &[u8], len: usize) -> Vec<[u8; 3]> {
+  let count = colors.len() / 3;
+  (0..len)
+    .map(|i| {
+      let position =
+        ((i as f32 + 0.5) * count as f32 / len as f32 - 0.5).clamp(0.0, (count - 1) as f32);
+      let (left, t) = (position.floor() as usize, position.fract());
+      let right = (left + 1).min(count - 1);
+
+      let mut color = [0; 3];
+      for ch in 0..3 {
+        let a = colors[left * 3 + ch] as f32;
+        let b = colors[right * 3 + ch] as f32;
+        color[ch] = (a + (b - a) * t).round() as u8;
+      }
+      color
+    })
+    .collect()
+}
@@ -0,0 +1,143 @@
+use crate::{shared::utils::stretch_colors, state::SliderLights};
+
+// LED strips driven over UDP, as understood by WLED and most pixel
+// controllers. The strip is the ground pixels, then the left air pixels and
+// the right air pixels, so WLED segments can be set up for each part.
+//
+// DDP packets are a 10 byte header of flags, sequence, data type, destination,
+// big endian byte offset and length, followed by up to 480 RGB pixels. The
+// last packet of a frame has the push flag so the frame is shown at once.
+// WLED realtime UDP packets in the DNRGB format are the protocol 4, a timeout
+// in seconds before WLED returns to its own effects, the big endian index of
+// the first pixel and up to 489 RGB pixels.
+
+pub const DDP_PORT: u16 = 4048;
+pub const WLED_UDP_PORT: u16 = 21324;
+
+/// Most pixels a strip can have.
+pub const MAX_PIXELS: usize = 4096;
+
+const DDP_MAX_PIXELS: usize = 480;
+const DDP_VERSION: u8 = 0x40;
+const DDP_PUSH: u8 = 0x01;
+const DDP_TYPE_RGB: u8 = 0x0b;
+const DDP_ID_DISPLAY: u8 = 1;
+
+const DNRGB_MAX_PIXELS: usize = 489;
+const DNRGB: u8 = 4;
+/// Seconds without packets before WLED goes back to its own effects.
+const DNRGB_TIMEOUT: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WledProtocol {
+  Ddp,
+  Udp,
+}
+
+impl WledProtocol {
+  pub fn from_str(s: &str) -> Option<Self> {
+    match s {
+      "ddp" => Some(WledProtocol::Ddp),
+      "udp" => Some(WledProtocol::Udp),
+      _ => None,
+    }
+  }
+
+  pub fn port(&self) -> u16 {
+    match self {
+      WledProtocol::Ddp => DDP_PORT,
+      WledProtocol::Udp => WLED_UDP_PORT,
+    }
+  }
+
+  /// Serializes a frame of pixels into as many packets as needed. The DDP
+  /// sequence is 1-15 and should change every frame.
+  pub fn serialize(&self, seq: u8, pixels: &[[u8; 3]]) -> Vec<Vec<u8>> {
+    match self {
+      WledProtocol::Ddp => {
+        let chunks = pixels.chunks(DDP_MAX_PIXELS);
+        let last = chunks.len().saturating_sub(1);
+        chunks
+          .enumerate()
+          .map(|(i, chunk)| {
+            let mut buf = Vec::with_capacity(10 + 3 * chunk.len());
+            buf.push(match i == last {
+              true => DDP_VERSION | DDP_PUSH,
+              false => DDP_VERSION,
+            });
+            buf.push(seq & 0x0f);
+            buf.push(DDP_TYPE_RGB);
+            buf.push(DDP_ID_DISPLAY);
+            buf.extend(((3 * i * DDP_MAX_PIXELS) as u32).to_be_bytes());
+            buf.extend(((3 * chunk.len()) as u16).to_be_bytes());
+            for pixel in chunk {
+              buf.extend(pixel);
+            }
+            buf
+          })
+          .collect()
+      }
+      WledProtocol::Udp => pixels
+        .chunks(DNRGB_MAX_PIXELS)
+        .enumerate()
+        .map(|(i, chunk)| {
+          let mut buf = Vec::with_capacity(4 + 3 * chunk.len());
+          buf.push(DNRGB);
+          buf.push(DNRGB_TIMEOUT);
+          buf.extend(((i * DNRGB_MAX_PIXELS) as u16).to_be_bytes());
+          for pixel in chunk {
+            buf.extend(pixel);
+          }
+          buf
+        })
+        .collect(),
+    }
+  }
+}
+
+/// How the slider colours fill a part of the strip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelLayout {
+  /// Spread once over the whole part, blending between colours.
+  Stretch,
+  /// Repeated from the start of the part.
+  Tile,
+}
+
+impl PixelLayout {
+  pub fn from_str(s: &str) -> Option<Self> {
+    match s {
+      "stretch" => Some(PixelLayout::Stretch),
+      "tile" => Some(PixelLayout::Tile),
+      _ => None,
+    }
+  }
+
+  fn fill(&self, colors: &[u8], len: usize) -> Vec<[u8; 3]> {
+    match self {
+      PixelLayout::Stretch => stretch_colors(colors, len),
+      PixelLayout::Tile => colors
+        .chunks(3)
+        .map(|x| [x[0], x[1], x[2]])
+        .cycle()
+        .take(len)
+        .collect(),
+    }
+  }
+}
+
+/// Lays the lights out on a strip of `ground` pixels followed by `air` pixels
+/// for each side.
+pub fn lights_to_pixels(
+  lights: &SliderLights,
+  ground: usize,
+  air: usize,
+  layout: PixelLayout,
+) -> Vec<[u8; 3]> {
+  let mut pixels = layout.fill(&lights.ground, ground);
+  if air > 0 {
+    pixels.extend(layout.fill(&lights.air_left, air));
+    pixels.extend(layout.fill(&lights.air_right, air));
+  }
+  pixels
+}
@@ -68,6 +68,13 @@ pub struct SliderLights {
   /// Internal dirty flag used to indicate that new lighting data is available.
   pub dirty: bool,
 
+  /// Brightness from 0 to 1 applied when lights are sent to a controller.
+  pub brightness: f32,
+
+  /// When lights were last received from a game, for lighting modes driven
+  /// by the game.
+  pub last_frame: Option<Instant>,
+
   /// To deprecate
   pub start: Instant,
 }
@@ -80,10 +87,38 @@ impl SliderLights {
       air_left: [0; 3 * 3],
       air_right: [0; 3 * 3],
       dirty: false,
+      brightness: 1.0,
+      last_frame: None,
       start: Instant::now(),
     }
   }
 
+  /// Makes a copy of the lighting state with brightness applied, used by
+  /// devices when sending lights to the controller.
+  pub fn output(&self) -> Self {
+    let mut lights = Self {
+      ground: self.ground,
+      air_left: self.air_left,
+      air_right: self.air_right,
+      dirty: self.dirty,
+      brightness: 1.0,
+      last_frame: self.last_frame,
+      start: self.start,
+    };
+    if self.brightness < 1.0 {
+      for x in lights
+        .ground
+        .iter_mut()
+        .chain(lights.air_left.iter_mut())
+        .chain(lights.air_right.iter_mut())
+      {
+        *x = (*x as f32 * self.brightness).round() as u8;
+      }
+    }
+
+    lights
+  }
+
   /// Apply a RGB color to some pixel in the lighting state.
   pub fn paint(&mut self, idx: usize, color: &[u8; 3]) {
     self.ground[3 * idx..3 * (idx + 1)].copy_from_slice(color);
@@ -138,6 +173,15 @@ impl SliderState {
     }
   }
 
+  /// Creates a state that shares input with this one but has its own
+  /// lighting, used to render a lighting layer before compositing.
+  pub fn fork_lights(&self) -> Self {
+    Self {
+      input: Arc::clone(&self.input),
+      lights: Arc::new(Mutex::new(SliderLights::new())),
+    }
+  }
+
   /// Takes an instantaneous slider controller state (input + lighting) as a
   /// `Vec<u8>` that can be used for visualisation.
   pub fn snapshot(&self) -> Vec<u8> {
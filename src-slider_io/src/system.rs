@@ -1,8 +1,19 @@
 use directories::ProjectDirs;
-use image::Luma;
+use image::{DynamicImage, ImageOutputFormat, Luma};
 use log::info;
-use qrcode::QrCode;
-use std::{error::Error, fs, path::PathBuf};
+use qrcode::{render::svg, QrCode};
+use std::{
+  error::Error,
+  fs,
+  net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+  path::{Component, Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum QrFormat {
+  Png,
+  Svg,
+}
 
 pub fn list_ips() -> Result<Vec<String>, Box<dyn Error>> {
   let mut ips = vec![];
@@ -25,27 +36,185 @@ fn get_config_dir() -> Option<Box<PathBuf>> {
   Some(Box::new(config_dir.to_path_buf()))
 }
 
-/// Generates a helper QR for connecting with brokenithm
-pub fn get_brokenithm_qr_path(port: Option<u16>) -> Option<Box<PathBuf>> {
-  let config_dir = get_config_dir()?;
-  let brokenithm_qr_path = config_dir.join("brokenithm.png");
-
-  let ips = list_ips().ok()?;
-  let link = "http://imp.ress.me/t/sshelper?d=".to_string()
-    + &ips
-      .into_iter()
-      .filter(|s| s.as_str().chars().filter(|x| *x == '.').count() == 3)
-      .map(|s| base64::encode_config(s, base64::URL_SAFE_NO_PAD))
-      .collect::<Vec<String>>()
-      .join(";")
-    + "&p="
-    + port.or(Some(1606)).unwrap().to_string().as_str();
+fn lan_rank(ip: &Ipv4Addr) -> u8 {
+  match ip.octets() {
+    [192, 168, _, _] => 0,
+    [10, _, _, _] => 1,
+    [172, b, _, _] if (16..32).contains(&b) => 2,
+    _ => 3,
+  }
+}
+
+/// Lists IPv4 addresses likely to be reachable from other devices on the local
+/// network, preferring private ranges over anything else.
+fn list_lan_ips() -> Vec<IpAddr> {
+  let mut ips = list_ips()
+    .unwrap_or_default()
+    .into_iter()
+    .filter_map(|s| s.parse::<Ipv4Addr>().ok())
+    .filter(|ip| !ip.is_loopback() && !ip.is_link_local() && !ip.is_unspecified())
+    .collect::<Vec<Ipv4Addr>>();
+  ips.sort_by_key(lan_rank);
+
+  ips.into_iter().map(IpAddr::V4).collect()
+}
+
+/// Lists IPv6 addresses likely to be reachable from other devices, skipping
+/// link-local ones which need a zone id to be usable in a link.
+fn list_lan_ipv6s() -> Vec<IpAddr> {
+  list_ips()
+    .unwrap_or_default()
+    .into_iter()
+    .filter_map(|s| s.parse::<Ipv6Addr>().ok())
+    .filter(|ip| !ip.is_loopback() && !ip.is_unspecified() && ip.segments()[0] & 0xffc0 != 0xfe80)
+    .map(IpAddr::V6)
+    .collect()
+}
+
+/// Picks the IPv4 address most likely to be reachable from other devices on
+/// the local network.
+pub fn get_lan_ip() -> Option<String> {
+  list_lan_ips().first().map(|ip| ip.to_string())
+}
+
+/// Lists the addresses a server listening on `bind` can be reached at from
+/// other devices, best candidate first.
+fn get_reachable_ips(bind: &[IpAddr]) -> Vec<IpAddr> {
+  let mut ips = vec![];
+  for ip in bind {
+    let candidates = match ip {
+      IpAddr::V4(x) if x.is_unspecified() => list_lan_ips(),
+      IpAddr::V6(x) if x.is_unspecified() => list_lan_ipv6s(),
+      x if x.is_loopback() => vec![],
+      x => vec![*x],
+    };
+    for candidate in candidates {
+      if !ips.contains(&candidate) {
+        ips.push(candidate);
+      }
+    }
+  }
+
+  ips
+}
+
+/// Generates a link for connecting with brokenithm, either directly to the
+/// best address the server listens on or through the helper page that lists
+/// every IPv4 address. An empty `bind` means every IPv4 interface.
+pub fn get_brokenithm_link(port: Option<u16>, bind: &[IpAddr], direct: bool) -> Option<String> {
+  let port = port.unwrap_or(1606);
+  let ips = match bind.is_empty() {
+    true => get_reachable_ips(&[IpAddr::V4(Ipv4Addr::UNSPECIFIED)]),
+    false => get_reachable_ips(bind),
+  };
+  let link = match direct {
+    true => format!("http://{}/", SocketAddr::new(*ips.first()?, port)),
+    false => {
+      "http://imp.ress.me/t/sshelper?d=".to_string()
+        + ips
+          .iter()
+          .filter(|ip| ip.is_ipv4())
+          .map(|ip| base64::encode_config(ip.to_string(), base64::URL_SAFE_NO_PAD))
+          .collect::<Vec<String>>()
+          .join(";")
+          .as_str()
+        + "&p="
+        + port.to_string().as_str()
+    }
+  };
   info!("Url generated {}", link);
+
+  Some(link)
+}
+
+/// Generates a helper QR for connecting with brokenithm
+pub fn get_brokenithm_qr(
+  port: Option<u16>,
+  bind: &[IpAddr],
+  direct: bool,
+  format: QrFormat,
+) -> Option<Vec<u8>> {
+  let link = get_brokenithm_link(port, bind, direct)?;
   let qr = QrCode::new(link).ok()?;
-  let image = qr.render::<Luma<u8>>().build();
-  image.save(brokenithm_qr_path.as_path()).ok()?;
 
-  return Some(Box::new(brokenithm_qr_path));
+  match format {
+    QrFormat::Png => {
+      let image = qr.render::<Luma<u8>>().build();
+      let mut buf: Vec<u8> = vec![];
+      DynamicImage::ImageLuma8(image)
+        .write_to(&mut buf, ImageOutputFormat::Png)
+        .ok()?;
+      Some(buf)
+    }
+    QrFormat::Svg => Some(
+      qr.render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build()
+        .into_bytes(),
+    ),
+  }
+}
+
+/// Get a directory inside the config directory (and create if it does not
+/// already exist).
+fn get_config_subdir(name: &str) -> Option<Box<PathBuf>> {
+  let config_dir = get_config_dir()?;
+  let subdir = config_dir.join(name);
+  fs::create_dir_all(subdir.as_path()).ok()?;
+
+  Some(Box::new(subdir))
+}
+
+/// Joins a user supplied relative path, such as a file name from the config,
+/// onto `dir`. Only plain relative paths are allowed so that the result cannot
+/// escape the directory.
+pub fn join_config_path(dir: &Path, name: &str) -> Option<PathBuf> {
+  let relative = Path::new(name);
+  if name.is_empty()
+    || !relative
+      .components()
+      .all(|c| matches!(c, Component::Normal(_)))
+  {
+    return None;
+  }
+
+  Some(dir.join(relative))
+}
+
+/// Get the directory for user supplied brokenithm web files (and create if it
+/// does not already exist). Files here take precedence over embedded ones.
+pub fn get_brokenithm_www_dir() -> Option<Box<PathBuf>> {
+  get_config_subdir("brokenithm-www")
+}
+
+/// Get the directory for keyboard input layout files (and create if it does
+/// not already exist).
+pub fn get_keyboard_layout_dir() -> Option<Box<PathBuf>> {
+  get_config_subdir("keyboard-layouts")
+}
+
+/// Get the directory for gamepad input layout files (and create if it does
+/// not already exist).
+pub fn get_gamepad_layout_dir() -> Option<Box<PathBuf>> {
+  get_config_subdir("gamepad-layouts")
+}
+
+/// Get the directory for MIDI input layout files (and create if it does not
+/// already exist).
+pub fn get_midi_layout_dir() -> Option<Box<PathBuf>> {
+  get_config_subdir("midi-layouts")
+}
+
+/// Get the directory for lighting effect definition files (and create if it
+/// does not already exist).
+pub fn get_lighting_effect_dir() -> Option<Box<PathBuf>> {
+  get_config_subdir("lighting-effects")
+}
+
+/// Get the directory for lighting recordings (and create if it does not
+/// already exist).
+pub fn get_lighting_recording_dir() -> Option<Box<PathBuf>> {
+  get_config_subdir("lighting-recordings")
 }
 
 pub fn get_log_file_path() -> Option<Box<PathBuf>> {
@@ -114,17 +114,27 @@ fn main() {
       });
 
       // Show brokenithm qr
+      let app_handle = app.handle();
       let config_clone = Arc::clone(&config);
       app.listen_global("openBrokenithmQr", move |_| {
-        let config_handle = config_clone.lock();
-        let brokenithm_qr_path = slider_io::get_brokenithm_qr_path(
+        let (port, bind, qr_direct) = {
+          let config_handle = config_clone.lock();
           config_handle
             .as_ref()
-            .map(|c| c.device_mode.get_port())
-            .unwrap_or(None),
-        );
-        if let Some(brokenithm_qr_path) = brokenithm_qr_path {
-          open::that(brokenithm_qr_path.as_path()).ok();
+            .map(|c| {
+              (
+                c.device_mode.get_port(),
+                c.device_mode.get_bind(),
+                c.device_mode.get_qr_direct(),
+              )
+            })
+            .unwrap_or((None, vec![], false))
+        };
+        let brokenithm_qr =
+          slider_io::get_brokenithm_qr(port, &bind, qr_direct, slider_io::QrFormat::Svg)
+            .and_then(|x| String::from_utf8(x).ok());
+        if let Some(brokenithm_qr) = brokenithm_qr {
+          app_handle.emit_all("showBrokenithmQr", brokenithm_qr).ok();
         }
       });
 
@@ -157,11 +167,13 @@ fn main() {
       let manager_clone = Arc::clone(&manager);
       app.listen_global("queryState", move |_| {
         // app_handle.emit_all("showState", "@@@");
-        let (snapshot, timer) = {
+        let (snapshot, timer, errors, brokenithm_status) = {
           let manager_handle = manager_clone.lock();
           (
             manager_handle.try_get_state().map(|x| x.snapshot()),
             manager_handle.get_timer_state(),
+            manager_handle.get_error_state(),
+            manager_handle.get_brokenithm_status(),
           )
         };
         match snapshot {
@@ -172,6 +184,10 @@ fn main() {
         }
 
         app_handle.emit_all("showTimerState", timer).ok();
+        app_handle.emit_all("showErrorState", errors).ok();
+        app_handle
+          .emit_all("showBrokenithmStatus", brokenithm_status)
+          .ok();
       });
 
       // Config set event
@@ -191,6 +207,25 @@ fn main() {
         }
       });
 
+      // Brokenithm config set event
+      let config_clone = Arc::clone(&config);
+      let manager_clone = Arc::clone(&manager);
+      app.listen_global("setBrokenithmConfig", move |event| {
+        let payload = event.payload().unwrap();
+        info!("Brokenithm config applied {}", payload);
+        if let Some(brokenithm_config) =
+          slider_io::device::config::BrokenithmConfig::from_str(payload)
+        {
+          let mut config_handle = config_clone.lock();
+          if let Some(config_handle_ref) = config_handle.as_mut() {
+            config_handle_ref.set_brokenithm(brokenithm_config.clone());
+            config_handle_ref.save();
+          }
+          let manager_handle = manager_clone.lock();
+          manager_handle.update_brokenithm_config(brokenithm_config);
+        }
+      });
+
       Ok(())
     })
     .build(tauri::generate_context!())